@@ -0,0 +1,18 @@
+#![no_main]
+extern crate bootinfo;
+#[macro_use]
+extern crate libfuzzer_sys;
+
+use std::io::Cursor;
+
+fuzz_target!(|data: &[u8]| {
+    let descriptors = bootinfo::register();
+    let max_range = descriptors.iter().map(|d| d.max_range).max().unwrap_or(0);
+
+    // Exercise the gzip detection, then every parser on whatever came out of it
+    if let Ok(bytes) = bootinfo::header_bytes(Cursor::new(data), max_range) {
+        for desc in &descriptors {
+            let _ = desc.parse(bytes.clone());
+        }
+    }
+});
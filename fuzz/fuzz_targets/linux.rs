@@ -0,0 +1,11 @@
+#![no_main]
+extern crate bootinfo;
+extern crate bytes;
+#[macro_use]
+extern crate libfuzzer_sys;
+
+use bootinfo::parsers::linux::Header;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Header::parse(bytes::Bytes::from(data));
+});
@@ -0,0 +1,19 @@
+#![no_main]
+extern crate bootinfo;
+extern crate bytes;
+#[macro_use]
+extern crate libfuzzer_sys;
+
+use bootinfo::parsers::linux::{EfiStub, Header, Payload};
+
+/// Keeps a payload that decompresses to far more than its size within libFuzzer's memory limit
+const MAX_KERNEL_SIZE: u64 = 4 << 20;
+
+fuzz_target!(|data: &[u8]| {
+    // Exercise the payload decompressors, the ELF analysis and the PE/COFF analysis behind any
+    // header that parses
+    if let Ok(header) = Header::parse(bytes::Bytes::from(data)) {
+        let _ = Payload::parse_with_limit(&header, data, MAX_KERNEL_SIZE);
+        let _ = EfiStub::parse(&header, data);
+    }
+});
@@ -0,0 +1,14 @@
+#![no_main]
+extern crate bootinfo;
+extern crate bytes;
+#[macro_use]
+extern crate libfuzzer_sys;
+
+use bootinfo::parsers::multiboot2::Header;
+
+fuzz_target!(|data: &[u8]| {
+    // Rendering covers the hex dump of unknown tags
+    if let Ok(header) = Header::parse(bytes::Bytes::from(data)) {
+        let _ = header.to_string();
+    }
+});
@@ -0,0 +1,21 @@
+#![no_main]
+extern crate bootinfo;
+extern crate bytes;
+#[macro_use]
+extern crate libfuzzer_sys;
+
+use bootinfo::parsers::linux::{Header, Payload};
+use bootinfo::parsers::uki::Uki;
+
+/// Keeps a payload that decompresses to far more than its size within libFuzzer's memory limit
+const MAX_KERNEL_SIZE: u64 = 4 << 20;
+
+fuzz_target!(|data: &[u8]| {
+    // Exercise the PE/COFF section table, the text sections behind it and the kernel inside
+    if let Ok(uki) = Uki::parse(data) {
+        let linux = uki.linux(data);
+        if let Ok(header) = Header::parse(bytes::Bytes::from(linux)) {
+            let _ = Payload::parse_with_limit(&header, linux, MAX_KERNEL_SIZE);
+        }
+    }
+});
@@ -0,0 +1,366 @@
+//! Readers for the block-based compression formats that have no streaming decoder crate: the
+//! lzop container around LZO1X, and the legacy LZ4 format used by the Linux kernel.
+
+use lz4_flex;
+use std::io::{self, Read};
+
+/// The largest block either format is allowed to decompress to, to bound memory use on corrupt
+/// input
+const MAX_BLOCK_SIZE: usize = 8 << 20;
+
+fn invalid_data<S: Into<String>>(msg: S) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+/// Fill `buf` completely, or report that the data ended at `Ok(false)` if nothing was read
+fn read_exact_or_eof<R: Read>(rdr: &mut R, buf: &mut [u8]) -> io::Result<bool> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match rdr.read(&mut buf[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(len) => filled += len,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
+
+fn read_u32_be<R: Read>(rdr: &mut R) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    rdr.read_exact(&mut buf)?;
+    Ok(u32::from_be_bytes(buf))
+}
+
+/// The most that a block of `MAX_BLOCK_SIZE` bytes can grow to when LZ4 fails to compress it
+const MAX_COMPRESSED_BLOCK_SIZE: usize = MAX_BLOCK_SIZE + MAX_BLOCK_SIZE / 255 + 16;
+
+/// Read `len` bytes of compressed data, refusing lengths that cannot be a real block
+fn read_block<R: Read>(rdr: &mut R, len: usize) -> io::Result<Vec<u8>> {
+    if len > MAX_COMPRESSED_BLOCK_SIZE {
+        return Err(invalid_data(format!("compressed block of {} bytes is too large", len)));
+    }
+    // Grow the block as the data arrives, so that a truncated stream which claims a large block
+    // does not allocate all of it
+    let mut block = vec![];
+    rdr.take(len as u64).read_to_end(&mut block)?;
+    if block.len() < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(block)
+}
+
+/// Serves the decompressed blocks produced by `next_block` as a single stream
+pub struct BlockReader<R, F> {
+    rdr: R,
+    next_block: F,
+    block: Vec<u8>,
+    pos: usize,
+    done: bool,
+}
+
+impl<R, F> Read for BlockReader<R, F>
+    where F: FnMut(&mut R) -> io::Result<Option<Vec<u8>>>
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.block.len() {
+            if self.done {
+                return Ok(0);
+            }
+            match (self.next_block)(&mut self.rdr)? {
+                Some(block) => {
+                    self.block = block;
+                    self.pos = 0;
+                }
+                None => self.done = true,
+            }
+        }
+        let len = buf.len().min(self.block.len() - self.pos);
+        buf[..len].copy_from_slice(&self.block[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+pub const LZ4_LEGACY_MAGIC: u32 = 0x184c_2102;
+
+/// Decompress the legacy LZ4 format: the magic, followed by blocks that each start with their
+/// compressed length
+pub fn lz4_legacy<R: Read>(rdr: R)
+                           -> BlockReader<R, impl FnMut(&mut R) -> io::Result<Option<Vec<u8>>>> {
+    let mut magic_seen = false;
+    let next_block = move |rdr: &mut R| -> io::Result<Option<Vec<u8>>> {
+        loop {
+            let mut len = [0u8; 4];
+            if !read_exact_or_eof(rdr, &mut len)? {
+                return Ok(None);
+            }
+            let len = u32::from_le_bytes(len);
+            // Concatenated streams each start with the magic again
+            if len == LZ4_LEGACY_MAGIC {
+                magic_seen = true;
+                continue;
+            }
+            if !magic_seen {
+                return Err(invalid_data("missing legacy lz4 magic"));
+            }
+            let block = read_block(rdr, len as usize)?;
+            let mut out = vec![0u8; MAX_BLOCK_SIZE];
+            let len = lz4_flex::block::decompress_into(&block, &mut out)
+                .map_err(|e| invalid_data(format!("corrupt lz4 block: {}", e)))?;
+            out.truncate(len);
+            return Ok(Some(out));
+        }
+    };
+    BlockReader {
+        rdr,
+        next_block,
+        block: vec![],
+        pos: 0,
+        done: false,
+    }
+}
+
+pub const LZOP_MAGIC: &[u8] = b"\x89LZO\x00\x0d\x0a\x1a\x0a";
+
+const F_ADLER32_D: u32 = 0x0000_0001;
+const F_ADLER32_C: u32 = 0x0000_0002;
+const F_H_EXTRA_FIELD: u32 = 0x0000_0040;
+const F_CRC32_D: u32 = 0x0000_0100;
+const F_CRC32_C: u32 = 0x0000_0200;
+const F_H_FILTER: u32 = 0x0000_0800;
+
+/// Skip over the lzop file header, returning its flags
+fn lzop_header<R: Read>(rdr: &mut R) -> io::Result<u32> {
+    let mut magic = [0u8; 9];
+    rdr.read_exact(&mut magic)?;
+    if magic != LZOP_MAGIC {
+        return Err(invalid_data("missing lzop magic"));
+    }
+    let mut fixed = [0u8; 4];
+    rdr.read_exact(&mut fixed)?;
+    let version = u16::from_be_bytes([fixed[0], fixed[1]]);
+    let skip = |len: usize, rdr: &mut R| -> io::Result<()> {
+        io::copy(&mut rdr.take(len as u64), &mut io::sink()).and_then(|copied| {
+            if copied == len as u64 {
+                Ok(())
+            } else {
+                Err(io::ErrorKind::UnexpectedEof.into())
+            }
+        })
+    };
+
+    // version_needed_to_extract, then method and level
+    skip(if version >= 0x0940 { 2 } else { 0 }, rdr)?;
+    let mut method = [0u8; 1];
+    rdr.read_exact(&mut method)?;
+    if !(1..=3).contains(&method[0]) {
+        return Err(invalid_data(format!("unsupported lzop method {}", method[0])));
+    }
+    skip(if version >= 0x0940 { 1 } else { 0 }, rdr)?;
+
+    let flags = read_u32_be(rdr)?;
+    if flags & F_H_FILTER != 0 {
+        return Err(invalid_data("lzop filters are not supported"));
+    }
+    // mode and mtime
+    skip(if version >= 0x0940 { 12 } else { 8 }, rdr)?;
+    let mut name_len = [0u8; 1];
+    rdr.read_exact(&mut name_len)?;
+    // name and header checksum
+    skip(usize::from(name_len[0]) + 4, rdr)?;
+    if flags & F_H_EXTRA_FIELD != 0 {
+        let len = read_u32_be(rdr)?;
+        // extra field and its checksum
+        skip(len as usize + 4, rdr)?;
+    }
+    Ok(flags)
+}
+
+/// Decompress an lzop file
+pub fn lzop<R: Read>(rdr: R) -> BlockReader<R, impl FnMut(&mut R) -> io::Result<Option<Vec<u8>>>> {
+    let mut header_flags = None;
+    let next_block = move |rdr: &mut R| -> io::Result<Option<Vec<u8>>> {
+        let flags = match header_flags {
+            Some(flags) => flags,
+            None => *header_flags.get_or_insert(lzop_header(rdr)?),
+        };
+        let dst_len = read_u32_be(rdr)? as usize;
+        if dst_len == 0 {
+            return Ok(None);
+        }
+        if dst_len > MAX_BLOCK_SIZE {
+            return Err(invalid_data(format!("lzop block of {} bytes is too large", dst_len)));
+        }
+        let src_len = read_u32_be(rdr)? as usize;
+        // Checksums are not verified, since the decompressor is already bounds-checked
+        let mut checksums = [F_ADLER32_D, F_CRC32_D]
+            .iter()
+            .filter(|&&flag| flags & flag != 0)
+            .count();
+        if src_len < dst_len {
+            checksums += [F_ADLER32_C, F_CRC32_C]
+                .iter()
+                .filter(|&&flag| flags & flag != 0)
+                .count();
+        }
+        read_block(rdr, checksums * 4)?;
+
+        let block = read_block(rdr, src_len)?;
+        if src_len == dst_len {
+            Ok(Some(block))
+        } else if src_len > dst_len {
+            Err(invalid_data("lzop block grew when compressed"))
+        } else {
+            lzo1x_decompress(&block, dst_len).map(Some)
+        }
+    };
+    BlockReader {
+        rdr,
+        next_block,
+        block: vec![],
+        pos: 0,
+        done: false,
+    }
+}
+
+/// Decompress a single LZO1X block that expands to at most `max_len` bytes
+pub fn lzo1x_decompress(input: &[u8], max_len: usize) -> io::Result<Vec<u8>> {
+    let corrupt = || invalid_data("corrupt lzo block");
+    let mut out: Vec<u8> = Vec::with_capacity(max_len);
+    let mut ip = 0;
+
+    let byte = |ip: &mut usize| -> io::Result<usize> {
+        let b = *input.get(*ip).ok_or_else(corrupt)?;
+        *ip += 1;
+        Ok(usize::from(b))
+    };
+    // A run length that continues into following bytes when its field in the instruction is 0
+    let run = |ip: &mut usize, base: usize| -> io::Result<usize> {
+        let mut len = 0usize;
+        while *input.get(*ip).ok_or_else(corrupt)? == 0 {
+            *ip += 1;
+            len = len.checked_add(255).ok_or_else(corrupt)?;
+        }
+        let last = usize::from(input[*ip]);
+        *ip += 1;
+        Ok(len + base + last)
+    };
+    let literals = |ip: &mut usize, out: &mut Vec<u8>, len: usize| -> io::Result<()> {
+        let end = ip.checked_add(len).ok_or_else(corrupt)?;
+        if out.len() + len > max_len {
+            return Err(corrupt());
+        }
+        out.extend_from_slice(input.get(*ip..end).ok_or_else(corrupt)?);
+        *ip = end;
+        Ok(())
+    };
+    let copy_match = |out: &mut Vec<u8>, distance: usize, len: usize| -> io::Result<()> {
+        if distance == 0 || distance > out.len() || out.len() + len > max_len {
+            return Err(corrupt());
+        }
+        // The match may overlap the bytes it produces, so copy a byte at a time
+        let start = out.len() - distance;
+        for index in 0..len {
+            let b = out[start + index];
+            out.push(b);
+        }
+        Ok(())
+    };
+
+    // The number of literals that followed the previous instruction, where 4 stands for a
+    // longer run
+    let mut state = 0;
+    if input.first().is_some_and(|&b| b > 17) {
+        let len = byte(&mut ip)? - 17;
+        literals(&mut ip, &mut out, len)?;
+        state = if len < 4 { len } else { 4 };
+    }
+
+    loop {
+        let t = byte(&mut ip)?;
+        let (distance, len, next) = if t < 16 {
+            if state == 0 {
+                let len = if t == 0 { run(&mut ip, 15)? } else { t } + 3;
+                literals(&mut ip, &mut out, len)?;
+                state = 4;
+                continue;
+            } else if state != 4 {
+                (1 + (t >> 2) + (byte(&mut ip)? << 2), 2, t & 3)
+            } else {
+                (1 + 0x800 + (t >> 2) + (byte(&mut ip)? << 2), 3, t & 3)
+            }
+        } else if t >= 64 {
+            (1 + ((t >> 2) & 7) + (byte(&mut ip)? << 3), (t >> 5) + 1, t & 3)
+        } else if t >= 32 {
+            let len = if t & 31 == 0 { run(&mut ip, 31)? } else { t & 31 } + 2;
+            let next = byte(&mut ip)? | byte(&mut ip)? << 8;
+            (1 + (next >> 2), len, next & 3)
+        } else {
+            let len = if t & 7 == 0 { run(&mut ip, 7)? } else { t & 7 } + 2;
+            let next = byte(&mut ip)? | byte(&mut ip)? << 8;
+            let distance = ((t & 8) << 11) + (next >> 2);
+            if distance == 0 {
+                // The end-of-stream marker
+                return if len == 3 && ip == input.len() {
+                           Ok(out)
+                       } else {
+                           Err(corrupt())
+                       };
+            }
+            (distance + 0x4000, len, next & 3)
+        };
+        copy_match(&mut out, distance, len)?;
+        literals(&mut ip, &mut out, next)?;
+        state = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lzo1x_literals_and_match() {
+        let block = [20, b'a', b'b', b'c', 232, 0, 17, 0, 0];
+        assert_eq!(lzo1x_decompress(&block, 64).unwrap(), b"abcabcabcab");
+    }
+
+    #[test]
+    fn lzo1x_long_literal_run() {
+        let block = [2, b'h', b'e', b'l', b'l', b'o', 17, 0, 0];
+        assert_eq!(lzo1x_decompress(&block, 64).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn lzo1x_rejects_match_before_start() {
+        let block = [20, b'a', b'b', b'c', 232, 1, 17, 0, 0];
+        assert!(lzo1x_decompress(&block, 64).is_err());
+    }
+
+    #[test]
+    fn lzo1x_rejects_overlong_output() {
+        let block = [20, b'a', b'b', b'c', 232, 0, 17, 0, 0];
+        assert!(lzo1x_decompress(&block, 8).is_err());
+    }
+
+    #[test]
+    fn lzop_file() {
+        let mut data = LZOP_MAGIC.to_vec();
+        // version, lib_version, version_needed_to_extract, method, level
+        data.extend(&[0x10, 0x30, 0x20, 0x80, 0x09, 0x40, 1, 5]);
+        // flags, mode, mtime_low, mtime_high
+        data.extend(&[0u8; 16]);
+        // name_len and header checksum
+        data.extend(&[0u8; 5]);
+        data.extend(&11u32.to_be_bytes());
+        data.extend(&9u32.to_be_bytes());
+        data.extend(&[20, b'a', b'b', b'c', 232, 0, 17, 0, 0]);
+        data.extend(&0u32.to_be_bytes());
+
+        let mut out = vec![];
+        lzop(&data[..]).read_to_end(&mut out).unwrap();
+        assert_eq!(out, b"abcabcabcab");
+    }
+}
@@ -0,0 +1,47 @@
+//! Parsers for the boot information embedded in binaries such as Multiboot-compliant kernels
+//! and Linux bzImages.
+//!
+//! Each supported format is exposed as a module under [`parsers`] with a typed `Header`, and is
+//! also registered as a [`Descriptor`] so that callers can try every known format in turn:
+//!
+//! ```no_run
+//! extern crate bootinfo;
+//!
+//! use std::fs::File;
+//!
+//! let descriptors = bootinfo::register();
+//! let max_range = descriptors.iter().map(|d| d.max_range).max().unwrap_or(0);
+//! let fp = File::open("/boot/xen.gz").unwrap();
+//! let bytes = bootinfo::header_bytes(fp, max_range).unwrap();
+//! for desc in &descriptors {
+//!     if let Ok(header) = desc.parse(bytes.clone()) {
+//!         println!("{}", header);
+//!     }
+//! }
+//! ```
+
+#[macro_use]
+extern crate bitflags;
+extern crate bytes;
+extern crate bzip2;
+#[macro_use]
+extern crate derive_error_chain;
+extern crate error_chain;
+extern crate flate2;
+extern crate lz4_flex;
+extern crate ruzstd;
+#[macro_use]
+extern crate serde_json;
+extern crate xz2;
+
+mod decoders;
+pub mod parsers;
+pub mod utils;
+
+pub use parsers::{register, register_with, BootInfo, Descriptor};
+pub use utils::header_bytes;
+
+#[derive(Debug, ErrorChain)]
+pub enum ErrorKind {
+    Msg(String),
+}
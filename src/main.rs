@@ -1,31 +1,87 @@
-#[macro_use]
-extern crate bitflags;
-extern crate bytes;
+extern crate bootinfo;
 extern crate clap;
 #[macro_use]
-extern crate derive_error_chain;
-#[macro_use]
 extern crate error_chain;
-extern crate flate2;
 #[macro_use]
 extern crate lazy_static;
+#[macro_use]
+extern crate serde_json;
 
-use clap::{App, Arg};
+use bootinfo::{parsers, BootInfo, Result, ResultExt};
+use bootinfo::utils::{detect_file_bytes, detect_header_bytes};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 use std::collections::HashSet;
-use std::fs::File;
-
-mod utils;
-mod parsers;
-
-use utils::header_bytes;
+use std::fs::{self, File};
+use std::io::{Seek, SeekFrom};
+use std::path::Path;
 
 lazy_static! {
     static ref INFO: Vec<parsers::Descriptor> = parsers::register();
 }
 
-#[derive(Debug, ErrorChain)]
-pub enum ErrorKind {
-    Msg(String),
+/// Read the whole (decompressed) image from `fp`, which the header bytes were already read from
+fn read_image(fp: &mut File) -> Result<bytes::Bytes> {
+    fp.seek(SeekFrom::Start(0))
+        .chain_err(|| "failed to seek back to beginning of file")?;
+    Ok(detect_file_bytes(fp)?.0)
+}
+
+/// Write one extracted component of `input` next to the others in `output_dir`
+fn write_component(output_dir: &Path, input: &str, suffix: &str, what: &str, data: &[u8])
+                   -> Result<()> {
+    let name = Path::new(input).file_name().map_or_else(|| input.into(), |n| n.to_string_lossy());
+    let path = output_dir.join(format!("{}.{}", name, suffix));
+    fs::write(&path, data).chain_err(|| format!("failed to write {}", path.display()))?;
+    println!("{}: {} ({} bytes)", path.display(), what, data.len());
+    Ok(())
+}
+
+/// Write the inner pieces of an image out to files
+fn extract(matches: &ArgMatches) -> Result<i32> {
+    let input = matches.value_of("INPUT").expect("INPUT is a required field");
+    let output_dir = Path::new(matches.value_of("output-dir").unwrap_or("."));
+
+    let fp = File::open(input)
+        .chain_err(|| format!("failed to open input file {}", input))?;
+    let (image, compression) = detect_file_bytes(fp)?;
+    let mut extracted = false;
+
+    if let Some(compression) = compression {
+        write_component(output_dir,
+                        input,
+                        "decompressed",
+                        &format!("{}-decompressed image", compression),
+                        &image)?;
+        extracted = true;
+    }
+
+    if let Ok(header) = parsers::linux::Header::parse(image.clone()) {
+        if let Some((setup, kernel)) = header.split(&image) {
+            write_component(output_dir, input, "setup", "real-mode setup sectors", setup)?;
+            write_component(output_dir, input, "kernel", "protected-mode kernel", kernel)?;
+            extracted = true;
+        }
+        match parsers::linux::Payload::parse(&header, &image) {
+            Ok(payload) => {
+                for warning in payload.warnings() {
+                    eprintln!("{}: Warning: {}", input, warning);
+                }
+                write_component(output_dir,
+                                input,
+                                "vmlinux",
+                                &format!("{}-decompressed kernel payload", payload.compression()),
+                                payload.kernel())?;
+                extracted = true;
+            }
+            Err(rejection) => eprintln!("{}: no kernel payload: {}", input, rejection),
+        }
+    }
+
+    if !extracted {
+        eprintln!("{}: nothing to extract", input);
+        return Ok(1);
+    }
+    Ok(0)
 }
 
 quick_main!{|| -> Result<i32> {
@@ -34,6 +90,7 @@ quick_main!{|| -> Result<i32> {
 
     let matches = App::new("bootinfo")
                 .about("Display boot information from a file")
+                .setting(AppSettings::SubcommandsNegateReqs)
                 .arg(Arg::with_name("quiet")
                      .short("q")
                      .long("quiet")
@@ -45,13 +102,68 @@ quick_main!{|| -> Result<i32> {
                      .long("only")
                      .help("only look for specific boot info types")
                      .possible_values(&possible_parsers))
+                .arg(Arg::with_name("diagnose")
+                     .long("diagnose")
+                     .help("explain why each boot info type was not found"))
+                .arg(Arg::with_name("all")
+                     .long("all")
+                     .help("report every valid header in the search window, not just the one a \
+                            boot loader would use"))
+                .arg(Arg::with_name("payload")
+                     .long("payload")
+                     .help("decompress and analyze the kernel inside a Linux bzImage"))
+                .arg(Arg::with_name("config")
+                     .long("config")
+                     .help("print the kernel config embedded with CONFIG_IKCONFIG"))
+                .arg(Arg::with_name("config-option")
+                     .takes_value(true)
+                     .multiple(true)
+                     .number_of_values(1)
+                     .long("config-option")
+                     .value_name("CONFIG_OPTION")
+                     .help("print the value of an option in the embedded kernel config"))
+                .arg(Arg::with_name("format")
+                     .takes_value(true)
+                     .long("format")
+                     .help("the format to print the header information in")
+                     .possible_values(&["text", "json"])
+                     .default_value("text"))
                 .arg(Arg::with_name("INPUT")
                      .required(true)
-                     .help("the input file to use"))
+                     .multiple(true)
+                     .help("the input files to use"))
+                .subcommand(SubCommand::with_name("extract")
+                            .about("Write the components of an image out to files")
+                            .long_about("Write the components of an image out to files named \
+                                         after the input:\n\n  \
+                                         .decompressed  the input with its compression removed\n  \
+                                         .setup         the real-mode setup sectors of a \
+                                         bzImage\n  \
+                                         .kernel        the protected-mode kernel of a bzImage\n  \
+                                         .vmlinux       the decompressed kernel payload of a \
+                                         bzImage")
+                            .arg(Arg::with_name("output-dir")
+                                 .takes_value(true)
+                                 .short("o")
+                                 .long("output-dir")
+                                 .help("the directory to write the components to")
+                                 .default_value("."))
+                            .arg(Arg::with_name("INPUT")
+                                 .required(true)
+                                 .help("the input file to extract from")))
                 .get_matches();
 
-    let input = matches.value_of("INPUT").expect("INPUT is a required field");
+    if let Some(matches) = matches.subcommand_matches("extract") {
+        return extract(matches);
+    }
+
+    let inputs: Vec<&str> = matches.values_of("INPUT")
+        .expect("INPUT is a required field")
+        .collect();
     let quiet = matches.is_present("quiet");
+    let json = matches.value_of("format") == Some("json");
+    let diagnose = matches.is_present("diagnose");
+    let all = matches.is_present("all");
 
     let allowed_parsers: Vec<&parsers::Descriptor> = match matches.values_of("only") {
         Some(only) => {
@@ -61,30 +173,218 @@ quick_main!{|| -> Result<i32> {
         None => INFO.iter().collect()
     };
 
+    // The payload and kernel_info can only be found through the Linux header
+    let linux = allowed_parsers.iter().any(|d| d.name == "linux");
+    let payload = matches.is_present("payload") && linux;
+
+    let print_config = matches.is_present("config");
+    let config_options: Vec<String> = matches.values_of("config-option")
+        .map(|options| {
+            options.map(|option| if option.starts_with("CONFIG_") {
+                                     option.to_string()
+                                 } else {
+                                     format!("CONFIG_{}", option)
+                                 })
+                   .collect()
+        })
+        .unwrap_or_default();
+    let want_config = print_config || !config_options.is_empty();
+
     // Grab the maximum range that the header can be found
     let max_range = allowed_parsers.iter().map(|d| d.max_range).max().unwrap_or(0);
 
-    // Get the possible header bytes out of the file
-    let fp = File::open(&input)
-        .chain_err(|| format!("failed to open input file {}", input))?;
+    let mut status = 0;
 
-    let bytes = header_bytes(fp, max_range)?;
+    for input in &inputs {
+        // Get the possible header bytes out of the file
+        let mut fp = File::open(input)
+            .chain_err(|| format!("failed to open input file {}", input))?;
 
-    // For each known descriptor
-    let headers: Vec<Box<parsers::BootInfo>> = allowed_parsers.iter().filter_map(|info| {
-        // Attempt to parse the possible header bytes as that type
-        info.parse(bytes.clone())
-    }).collect();
+        let (bytes, compression) = detect_header_bytes(&mut fp, max_range)?;
+        // Anything shorter than the window is already the whole image
+        let mut image = if bytes.len() < max_range {
+            Some(bytes.clone())
+        } else {
+            None
+        };
 
-    // If we are not simply checking for presence
-    if !quiet {
-        for header in &headers {
-            // Print the header fields out
-            println!("{}", header);
+        let mut headers: Vec<Box<dyn parsers::BootInfo>> = vec![];
+        let mut rejections: Vec<(&str, parsers::Rejection)> = vec![];
+
+        // A UKI carries the kernel that actually boots in its .linux section, well past the
+        // header bytes, so describe that kernel instead of the stub in front of it
+        let mut uki_kernel = None;
+        if parsers::uki::is_uki(&bytes) {
+            let whole = match image {
+                Some(ref whole) => whole.clone(),
+                None => read_image(&mut fp)?,
+            };
+            match parsers::uki::Uki::parse(&whole) {
+                Ok(uki) => {
+                    let offset = uki.linux_offset();
+                    uki_kernel = Some(whole.slice(offset, offset + uki.linux(&whole).len()));
+                    headers.push(Box::new(uki));
+                }
+                Err(rejection) => rejections.push(("uki", rejection)),
+            }
+            image = Some(whole);
         }
-    }
+        let bytes = match uki_kernel {
+            Some(ref kernel) => kernel.slice_to(kernel.len().min(max_range)),
+            None => bytes,
+        };
 
-    let status = if headers.is_empty() { 1 } else { 0 };
+        // For each known descriptor
+        for info in &allowed_parsers {
+            // Attempt to parse the possible header bytes as that type
+            let parsed = if all {
+                info.parse_all(bytes.clone())
+            } else {
+                info.parse(bytes.clone()).map(|header| vec![header])
+            };
+            match parsed {
+                Ok(found) => headers.extend(found),
+                Err(rejection) => rejections.push((info.name, rejection)),
+            }
+        }
+
+        let linux_header = parsers::linux::Header::parse(bytes.clone()).ok();
+        let kernel_info = linux &&
+                          linux_header.as_ref().is_some_and(|h| h.kernel_info_offset().is_some());
+
+        let mut config = None;
+        if payload || want_config || kernel_info {
+            // These lie well past the header bytes, so read in the whole image
+            let image = match (uki_kernel.as_ref(), image) {
+                (Some(kernel), _) => kernel.clone(),
+                (None, Some(whole)) => whole,
+                (None, None) => read_image(&mut fp)?,
+            };
+            if let (true, Some(header)) = (kernel_info, linux_header.as_ref()) {
+                match parsers::linux::KernelInfo::parse(header, &image) {
+                    Ok(kernel_info) => headers.push(Box::new(kernel_info)),
+                    Err(rejection) => rejections.push(("linux-kernel-info", rejection)),
+                }
+            }
+            let analyzed = match linux_header {
+                Some(ref header) if payload || want_config => {
+                    Some(parsers::linux::Payload::parse(header, &image))
+                }
+                _ => None,
+            };
+
+            if want_config {
+                // Look inside the kernel payload of a bzImage, or the image itself otherwise
+                config = Some(match analyzed {
+                                  Some(Ok(ref payload)) if payload.config().is_ok() => {
+                                      payload.config().cloned().map_err(Clone::clone)
+                                  }
+                                  _ => parsers::ikconfig::KernelConfig::find(&image),
+                              });
+            }
+            if payload {
+                match analyzed {
+                    Some(Ok(payload)) => headers.push(Box::new(payload)),
+                    Some(Err(rejection)) => rejections.push(("linux-payload", rejection)),
+                    None => {}
+                }
+            }
+        }
+        let config = match config {
+            Some(Ok(config)) => Some(config),
+            Some(Err(rejection)) => {
+                eprintln!("{}: no embedded kernel config: {}", input, rejection);
+                rejections.push(("ikconfig", rejection));
+                status = 1;
+                None
+            }
+            None => None,
+        };
+
+        if json {
+            let compression = compression.map(|c| c.to_string());
+            let mut document = json!({ "file": input, "compression": compression });
+            // If we are not simply checking for presence
+            if !quiet {
+                let headers: Vec<_> = headers.iter()
+                    .map(|h| parsers::fields::to_json(&**h))
+                    .collect();
+                document["headers"] = headers.into();
+            }
+            if diagnose {
+                let rejections: Vec<_> = rejections.iter().map(|&(name, ref rejection)| {
+                    match *rejection {
+                        parsers::Rejection::NotPresent => {
+                            json!({ "format": name, "status": "not-present" })
+                        }
+                        parsers::Rejection::Invalid { offset, ref reason } => {
+                            json!({ "format": name, "status": "invalid", "offset": offset,
+                                    "reason": reason })
+                        }
+                    }
+                }).collect();
+                document["rejections"] = rejections.into();
+            }
+            if let Some(ref config) = config {
+                if print_config {
+                    document["config"] = config.text().into();
+                }
+                if !config_options.is_empty() {
+                    let options: serde_json::Map<String, serde_json::Value> = config_options
+                        .iter()
+                        .map(|option| (option.clone(), config.get(option).into()))
+                        .collect();
+                    document["config_options"] = options.into();
+                }
+            }
+            if !quiet || diagnose || config.is_some() {
+                println!("{}", serde_json::to_string_pretty(&document)
+                         .chain_err(|| "failed to serialize header information")?);
+            }
+        } else {
+            // Name each file when there is more than one to tell apart
+            if inputs.len() > 1 && (!quiet || diagnose) {
+                println!("{}:", input);
+            }
+            // If we are not simply checking for presence
+            if !quiet {
+                if let Some(compression) = compression {
+                    println!("Offsets are relative to the {}-decompressed data\n", compression);
+                }
+                if uki_kernel.is_some() {
+                    println!("Offsets after the Unified Kernel Image are relative to its .linux \
+                              section\n");
+                }
+                for header in &headers {
+                    // Print the header fields out
+                    println!("{}", header);
+                }
+            }
+            if diagnose && !rejections.is_empty() {
+                let width = rejections.iter().map(|&(name, _)| name.len()).max().unwrap_or(0);
+                println!("Diagnostics");
+                for &(name, ref rejection) in &rejections {
+                    println!("  {:width$} : {}", name, rejection, width = width);
+                }
+                println!();
+            }
+            if let Some(ref config) = config {
+                if print_config {
+                    print!("{}", config.text());
+                }
+                for option in &config_options {
+                    match config.get(option) {
+                        Some(value) => println!("{}={}", option, value),
+                        None => println!("# {} is not set", option),
+                    }
+                }
+            }
+        }
+
+        if headers.is_empty() {
+            status = 1;
+        }
+    }
 
     Ok(status)
 }}
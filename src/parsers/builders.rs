@@ -0,0 +1,102 @@
+//! Builders for the synthetic images that the tests of more than one parser start from
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use parsers::{elf, ikconfig, pe};
+use std::io::Write;
+
+/// Build a little-endian ELF64 executable with a loadable segment and a build ID note
+pub fn elf64(entry: u64, build_id: &[u8]) -> Vec<u8> {
+    let mut data = vec![0u8; 64 + 2 * 56];
+    data[..4].copy_from_slice(elf::MAGIC);
+    data[4] = 2;
+    data[5] = 1;
+    data[6] = 1;
+    data[16..18].copy_from_slice(&2u16.to_le_bytes());
+    data[18..20].copy_from_slice(&62u16.to_le_bytes());
+    data[24..32].copy_from_slice(&entry.to_le_bytes());
+    data[32..40].copy_from_slice(&64u64.to_le_bytes());
+    data[54..56].copy_from_slice(&56u16.to_le_bytes());
+    data[56..58].copy_from_slice(&2u16.to_le_bytes());
+
+    let mut note: Vec<u8> = vec![];
+    note.extend(&4u32.to_le_bytes());
+    note.extend(&(build_id.len() as u32).to_le_bytes());
+    note.extend(&elf::NT_GNU_BUILD_ID.to_le_bytes());
+    note.extend(b"GNU\0");
+    note.extend(build_id);
+    let note_offset = data.len() as u64;
+
+    let phdrs: [(u32, u32, u64, u64, u64); 2] =
+        [(1, 5, 0, 0x0100_0000, data.len() as u64),
+         (elf::PT_NOTE, 4, note_offset, 0, note.len() as u64)];
+    for (index, &(typ, flags, offset, addr, size)) in phdrs.iter().enumerate() {
+        let phdr = &mut data[64 + index * 56..64 + (index + 1) * 56];
+        phdr[0..4].copy_from_slice(&typ.to_le_bytes());
+        phdr[4..8].copy_from_slice(&flags.to_le_bytes());
+        phdr[8..16].copy_from_slice(&offset.to_le_bytes());
+        phdr[16..24].copy_from_slice(&addr.to_le_bytes());
+        phdr[24..32].copy_from_slice(&addr.to_le_bytes());
+        phdr[32..40].copy_from_slice(&size.to_le_bytes());
+        phdr[40..48].copy_from_slice(&size.to_le_bytes());
+    }
+    data.extend(note);
+    data
+}
+
+/// A kernel config as the build writes it, with options of every kind
+pub const CONFIG: &str = "#\n# Automatically generated file; DO NOT EDIT.\n#\n\
+                          CONFIG_64BIT=y\nCONFIG_LOCALVERSION=\"-test\"\n\
+                          # CONFIG_RELOCATABLE is not set\nCONFIG_EFI_STUB=y\n\
+                          CONFIG_EXT4_FS=m\n";
+
+/// `config` compressed and wrapped in the markers, as the kernel build embeds it
+pub fn embedded(config: &str) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    encoder.write_all(config.as_bytes()).unwrap();
+    let mut data = ikconfig::START.to_vec();
+    data.extend(encoder.finish().unwrap());
+    data.extend(ikconfig::END);
+    data
+}
+
+/// Build a PE32+ EFI application whose headers start at `pe_offset` and that has the given
+/// sections, as (name, virtual address, virtual size, file offset, file size) entries
+pub fn pe32_plus(pe_offset: usize, entry: u32, sections: &[(&str, u32, u32, u32, u32)])
+                 -> Vec<u8> {
+    let optional_size = 112 + 6 * 8;
+    let table = pe_offset + pe::COFF_HEADER_SIZE + optional_size;
+    let mut data = vec![0u8; table + sections.len() * pe::SECTION_HEADER_SIZE];
+    data[..2].copy_from_slice(pe::MZ_MAGIC);
+    data[pe::PE_OFFSET_OFFSET..pe::PE_OFFSET_OFFSET + 4]
+        .copy_from_slice(&(pe_offset as u32).to_le_bytes());
+
+    let coff = pe_offset + 4;
+    data[pe_offset..coff].copy_from_slice(pe::PE_MAGIC);
+    data[coff..coff + 2].copy_from_slice(&pe::MACHINE_AMD64.to_le_bytes());
+    data[coff + 2..coff + 4].copy_from_slice(&(sections.len() as u16).to_le_bytes());
+    data[coff + 16..coff + 18].copy_from_slice(&(optional_size as u16).to_le_bytes());
+    data[coff + 18..coff + 20].copy_from_slice(&0x206u16.to_le_bytes());
+
+    let optional = pe_offset + pe::COFF_HEADER_SIZE;
+    data[optional..optional + 2].copy_from_slice(&pe::PE32_PLUS_MAGIC.to_le_bytes());
+    data[optional + 16..optional + 20].copy_from_slice(&entry.to_le_bytes());
+    data[optional + 32..optional + 36].copy_from_slice(&0x20u32.to_le_bytes());
+    data[optional + 36..optional + 40].copy_from_slice(&0x20u32.to_le_bytes());
+    let end = sections.iter().map(|s| s.1 + s.2).max().unwrap_or(0);
+    data[optional + 56..optional + 60].copy_from_slice(&end.to_le_bytes());
+    data[optional + 60..optional + 64].copy_from_slice(&0x200u32.to_le_bytes());
+    data[optional + 68..optional + 70].copy_from_slice(&10u16.to_le_bytes());
+    data[optional + 108..optional + 112].copy_from_slice(&6u32.to_le_bytes());
+
+    for (index, &(name, vaddr, vsize, offset, size)) in sections.iter().enumerate() {
+        let header = &mut data[table + index * pe::SECTION_HEADER_SIZE..];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[8..12].copy_from_slice(&vsize.to_le_bytes());
+        header[12..16].copy_from_slice(&vaddr.to_le_bytes());
+        header[16..20].copy_from_slice(&size.to_le_bytes());
+        header[20..24].copy_from_slice(&offset.to_le_bytes());
+        header[36..40].copy_from_slice(&0x6000_0020u32.to_le_bytes());
+    }
+    data
+}
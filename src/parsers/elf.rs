@@ -0,0 +1,474 @@
+//! Analysis of ELF images, such as the vmlinux inside a bzImage payload.
+//!
+//! This is not a registered boot info format; other parsers use it to describe the kernel image
+//! that their header points at.
+
+use super::{Field, Group, Rejection, Value};
+
+pub const MAGIC: &[u8] = b"\x7fELF";
+
+pub(crate) const PT_NOTE: u32 = 4;
+pub(crate) const NT_GNU_BUILD_ID: u32 = 3;
+
+/// The size of the ELF identification bytes at the start of the file
+const EI_NIDENT: usize = 16;
+
+/// The width of the addresses and offsets in the image
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Class {
+    Elf32,
+    Elf64,
+}
+
+/// A bounds-checked view of the image in its own byte order
+struct Reader<'a> {
+    data: &'a [u8],
+    class: Class,
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&self, offset: usize, len: usize) -> Result<&'a [u8], Rejection> {
+        offset.checked_add(len)
+            .and_then(|end| self.data.get(offset..end))
+            .ok_or_else(|| {
+                            Rejection::invalid(offset,
+                                               format!("ELF image is truncated, needed {} bytes",
+                                                       len))
+                        })
+    }
+
+    fn uint(&self, offset: usize, len: usize) -> Result<u64, Rejection> {
+        let bytes = self.bytes(offset, len)?;
+        let fold = |value: u64, byte: &u8| value << 8 | u64::from(*byte);
+        Ok(if self.big_endian {
+               bytes.iter().fold(0, fold)
+           } else {
+               bytes.iter().rev().fold(0, fold)
+           })
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16, Rejection> {
+        self.uint(offset, 2).map(|value| value as u16)
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32, Rejection> {
+        self.uint(offset, 4).map(|value| value as u32)
+    }
+
+    /// A word that is 32 or 64 bits wide depending on the class of the image
+    fn word(&self, offset: usize) -> Result<u64, Rejection> {
+        match self.class {
+            Class::Elf32 => self.uint(offset, 4),
+            Class::Elf64 => self.uint(offset, 8),
+        }
+    }
+}
+
+/// A program header, describing a segment of the image
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Segment {
+    offset: usize,
+    typ: u32,
+    flags: u32,
+    file_offset: u64,
+    vaddr: u64,
+    paddr: u64,
+    filesz: u64,
+    memsz: u64,
+    align: u64,
+}
+
+/// The parts of an ELF image that matter for booting it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Elf {
+    class: Class,
+    big_endian: bool,
+    typ: u16,
+    machine: u16,
+    entry: u64,
+    segments: Vec<Segment>,
+    build_id: Option<Vec<u8>>,
+}
+
+impl Elf {
+    /// Parse the ELF image at the start of `data`
+    ///
+    /// The data may be cut short: anything that lies past the end of it, such as the notes
+    /// holding the build ID, is left out rather than rejected.
+    pub fn parse(data: &[u8]) -> Result<Elf, Rejection> {
+        if !data.starts_with(MAGIC) {
+            return Err(Rejection::NotPresent);
+        }
+        if data.len() < EI_NIDENT {
+            return Err(Rejection::invalid(0, "ELF identification is truncated"));
+        }
+        let class = match data[4] {
+            1 => Class::Elf32,
+            2 => Class::Elf64,
+            other => return Err(Rejection::invalid(4, format!("unknown ELF class {}", other))),
+        };
+        let big_endian = match data[5] {
+            1 => false,
+            2 => true,
+            other => {
+                return Err(Rejection::invalid(5, format!("unknown ELF byte order {}", other)))
+            }
+        };
+        let rdr = Reader {
+            data,
+            class,
+            big_endian,
+        };
+
+        let typ = rdr.u16(16)?;
+        let machine = rdr.u16(18)?;
+        let entry = rdr.word(24)?;
+        let (phoff, phentsize, phnum) = match class {
+            Class::Elf32 => (rdr.word(28)?, rdr.u16(42)?, rdr.u16(44)?),
+            Class::Elf64 => (rdr.word(32)?, rdr.u16(54)?, rdr.u16(56)?),
+        };
+
+        let min_phentsize = match class {
+            Class::Elf32 => 32,
+            Class::Elf64 => 56,
+        };
+        if phnum != 0 && phentsize < min_phentsize {
+            return Err(Rejection::invalid(0,
+                                          format!("program header size {} is too small",
+                                                  phentsize)));
+        }
+
+        let mut segments = vec![];
+        for index in 0..u64::from(phnum) {
+            let offset = phoff.checked_add(index * u64::from(phentsize))
+                .filter(|&offset| offset <= data.len() as u64)
+                .ok_or_else(|| {
+                                Rejection::invalid(0,
+                                                   "program headers run past the end of the data")
+                            })? as usize;
+            segments.push(Segment::parse(&rdr, offset)?);
+        }
+
+        let build_id = segments.iter()
+            .filter(|segment| segment.typ == PT_NOTE)
+            .filter_map(|segment| find_build_id(&rdr, segment))
+            .next();
+
+        Ok(Elf {
+               class,
+               big_endian,
+               typ,
+               machine,
+               entry,
+               segments,
+               build_id,
+           })
+    }
+
+    /// The width of the addresses and offsets in the image
+    pub fn class(&self) -> Class {
+        self.class
+    }
+
+    /// Whether the image is stored most significant byte first
+    pub fn big_endian(&self) -> bool {
+        self.big_endian
+    }
+
+    /// The kind of object file, such as an executable
+    pub fn typ(&self) -> u16 {
+        self.typ
+    }
+
+    /// The architecture the image was built for
+    pub fn machine(&self) -> u16 {
+        self.machine
+    }
+
+    /// The virtual address that execution starts at
+    pub fn entry(&self) -> u64 {
+        self.entry
+    }
+
+    /// The segments described by the program headers
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// The GNU build ID, if the notes holding it are within the data
+    pub fn build_id(&self) -> Option<&[u8]> {
+        self.build_id.as_deref()
+    }
+
+    /// An address or offset, shown as wide as the class of the image makes it
+    pub fn address(&self, value: u64) -> Value {
+        Value::Hex {
+            value,
+            digits: match self.class {
+                Class::Elf32 => 8,
+                Class::Elf64 => 16,
+            },
+        }
+    }
+
+    /// The class of the image, as `fields` shows it
+    pub fn class_field(&self) -> Field {
+        Field::new("elf_class",
+                   "ELF Class",
+                   Value::Enum {
+                       value: match self.class {
+                           Class::Elf32 => 1,
+                           Class::Elf64 => 2,
+                       },
+                       name: match self.class {
+                           Class::Elf32 => "ELF32",
+                           Class::Elf64 => "ELF64",
+                       },
+                   })
+    }
+
+    /// A program header, as `fields` shows it
+    pub fn segment_group(&self, segment: &Segment) -> Group {
+        Group {
+            title: format!("Segment: {}", segment_name(segment.typ)),
+            fields: segment.fields(self),
+        }
+    }
+
+    pub fn fields(&self) -> Vec<Field> {
+        let mut fields = vec![self.class_field(),
+                              Field::new("elf_type",
+                                         "ELF Type",
+                                         Value::Enum {
+                                             value: u64::from(self.typ),
+                                             name: type_name(self.typ),
+                                         }),
+                              Field::new("machine",
+                                         "Machine",
+                                         Value::Enum {
+                                             value: u64::from(self.machine),
+                                             name: machine_name(self.machine),
+                                         }),
+                              Field::new("entry", "Entry", self.address(self.entry))];
+        if let Some(ref build_id) = self.build_id {
+            let hex: Vec<String> = build_id.iter().map(|b| format!("{:02x}", b)).collect();
+            fields.push(Field::new("build_id", "Build ID", Value::Text(hex.concat())));
+        }
+        fields.push(Field::new("segments",
+                               "Segments",
+                               Value::Tags(self.segments
+                                               .iter()
+                                               .map(|segment| self.segment_group(segment))
+                                               .collect())));
+        fields
+    }
+}
+
+impl Segment {
+    fn parse(rdr: &Reader, offset: usize) -> Result<Segment, Rejection> {
+        Ok(match rdr.class {
+               Class::Elf32 => {
+                   Segment {
+                       offset,
+                       typ: rdr.u32(offset)?,
+                       file_offset: rdr.word(offset + 4)?,
+                       vaddr: rdr.word(offset + 8)?,
+                       paddr: rdr.word(offset + 12)?,
+                       filesz: rdr.word(offset + 16)?,
+                       memsz: rdr.word(offset + 20)?,
+                       flags: rdr.u32(offset + 24)?,
+                       align: rdr.word(offset + 28)?,
+                   }
+               }
+               Class::Elf64 => {
+                   Segment {
+                       offset,
+                       typ: rdr.u32(offset)?,
+                       flags: rdr.u32(offset + 4)?,
+                       file_offset: rdr.word(offset + 8)?,
+                       vaddr: rdr.word(offset + 16)?,
+                       paddr: rdr.word(offset + 24)?,
+                       filesz: rdr.word(offset + 32)?,
+                       memsz: rdr.word(offset + 40)?,
+                       align: rdr.word(offset + 48)?,
+                   }
+               }
+           })
+    }
+
+    /// The offset of the program header within the image
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The kind of segment, such as loadable or notes
+    pub fn typ(&self) -> u32 {
+        self.typ
+    }
+
+    /// The read, write and execute permissions of the segment
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+
+    /// The offset of the segment contents within the image
+    pub fn file_offset(&self) -> u64 {
+        self.file_offset
+    }
+
+    /// The virtual address the segment is loaded at
+    pub fn vaddr(&self) -> u64 {
+        self.vaddr
+    }
+
+    /// The physical address the segment is loaded at
+    pub fn paddr(&self) -> u64 {
+        self.paddr
+    }
+
+    /// The size of the segment contents within the image
+    pub fn filesz(&self) -> u64 {
+        self.filesz
+    }
+
+    /// The size of the segment once loaded, including any zero-filled space
+    pub fn memsz(&self) -> u64 {
+        self.memsz
+    }
+
+    /// The alignment of the segment in memory and within the image
+    pub fn align(&self) -> u64 {
+        self.align
+    }
+
+    fn fields(&self, elf: &Elf) -> Vec<Field> {
+        let names = [(4, "read"), (2, "write"), (1, "execute")]
+            .iter()
+            .filter(|&&(bit, _)| self.flags & bit != 0)
+            .map(|&(_, name)| name)
+            .collect();
+        vec![Field::new("offset", "Offset", Value::offset(self.offset)),
+             Field::new("flags",
+                        "Flags",
+                        Value::Flags {
+                            bits: u64::from(self.flags),
+                            digits: 8,
+                            names,
+                        }),
+             Field::new("file_offset", "File Offset", elf.address(self.file_offset)),
+             Field::new("vaddr", "Virt Addr", elf.address(self.vaddr)),
+             Field::new("paddr", "Phys Addr", elf.address(self.paddr)),
+             Field::new("filesz", "File Size", Value::Size(self.filesz)),
+             Field::new("memsz", "Mem Size", Value::Size(self.memsz)),
+             Field::new("align", "Align", elf.address(self.align))]
+    }
+}
+
+/// Look through the notes in a segment for the GNU build ID
+fn find_build_id(rdr: &Reader, segment: &Segment) -> Option<Vec<u8>> {
+    let start = segment.file_offset as usize;
+    let end = start.checked_add(segment.filesz as usize)?.min(rdr.data.len());
+    let align4 = |len: usize| len.checked_add(3).map(|len| len & !3);
+
+    let mut offset = start;
+    while offset.checked_add(12)? <= end {
+        let namesz = rdr.u32(offset).ok()? as usize;
+        let descsz = rdr.u32(offset + 4).ok()? as usize;
+        let typ = rdr.u32(offset + 8).ok()?;
+        let name = offset + 12;
+        let desc = name.checked_add(align4(namesz)?)?;
+        let next = desc.checked_add(align4(descsz)?)?;
+        if next > end {
+            return None;
+        }
+        if typ == NT_GNU_BUILD_ID && rdr.bytes(name, namesz).ok()? == b"GNU\0" {
+            return rdr.bytes(desc, descsz).ok().map(|id| id.to_vec());
+        }
+        offset = next;
+    }
+    None
+}
+
+fn type_name(typ: u16) -> &'static str {
+    match typ {
+        1 => "relocatable",
+        2 => "executable",
+        3 => "shared object",
+        4 => "core",
+        _ => "unknown",
+    }
+}
+
+fn machine_name(machine: u16) -> &'static str {
+    match machine {
+        3 => "x86",
+        8 => "MIPS",
+        20 => "PowerPC",
+        21 => "PowerPC64",
+        22 => "s390",
+        40 => "ARM",
+        62 => "x86-64",
+        183 => "AArch64",
+        243 => "RISC-V",
+        258 => "LoongArch",
+        _ => "unknown",
+    }
+}
+
+fn segment_name(typ: u32) -> &'static str {
+    match typ {
+        0 => "NULL",
+        1 => "LOAD",
+        2 => "DYNAMIC",
+        3 => "INTERP",
+        4 => "NOTE",
+        5 => "SHLIB",
+        6 => "PHDR",
+        7 => "TLS",
+        0x6474_e550 => "GNU_EH_FRAME",
+        0x6474_e551 => "GNU_STACK",
+        0x6474_e552 => "GNU_RELRO",
+        0x6474_e553 => "GNU_PROPERTY",
+        _ => "unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parsers::builders::elf64;
+
+    #[test]
+    fn parse_elf64() {
+        let elf = Elf::parse(&elf64(0x0100_0000, &[0xde, 0xad, 0xbe, 0xef])).unwrap();
+        assert_eq!(elf.class(), Class::Elf64);
+        assert_eq!(elf.machine(), 62);
+        assert_eq!(elf.entry(), 0x0100_0000);
+        assert_eq!(elf.segments().len(), 2);
+        assert_eq!(elf.segments()[0].flags(), 5);
+        assert_eq!(elf.build_id(), Some(&[0xde, 0xad, 0xbe, 0xef][..]));
+    }
+
+    #[test]
+    fn parse_elf64_without_notes() {
+        let data = elf64(0x0100_0000, &[0xde, 0xad, 0xbe, 0xef]);
+        let elf = Elf::parse(&data[..64 + 2 * 56]).unwrap();
+        assert_eq!(elf.segments().len(), 2);
+        assert_eq!(elf.build_id(), None);
+    }
+
+    #[test]
+    fn reject_truncated_program_headers() {
+        let data = elf64(0x0100_0000, &[]);
+        match Elf::parse(&data[..64 + 56 + 8]) {
+            Err(Rejection::Invalid { offset, .. }) => assert_eq!(offset, 64 + 56 + 8),
+            other => panic!("unexpected outcome {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reject_non_elf() {
+        assert_eq!(Elf::parse(b"MZ\x90\x00"), Err(Rejection::NotPresent));
+    }
+}
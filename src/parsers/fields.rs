@@ -0,0 +1,241 @@
+//! A format-independent description of a parsed header.
+//!
+//! Every parser describes its header as an ordered list of named, typed fields so that the same
+//! information can be rendered as text or any other output format.
+
+use serde_json::{self, Map};
+use std::fmt;
+
+/// The value of a single header field
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    /// A plain number, shown in decimal
+    Integer(u64),
+    /// A number shown in hexadecimal, such as an address, magic value or checksum, padded to
+    /// `digits` digits
+    Hex { value: u64, digits: usize },
+    /// A count of bytes
+    Size(u64),
+    /// A yes or no answer
+    Bool(bool),
+    /// A number with a well-known meaning
+    Enum { value: u64, name: &'static str },
+    /// A set of flags, along with the names of the flags that are set
+    Flags {
+        bits: u64,
+        digits: usize,
+        names: Vec<&'static str>,
+    },
+    /// Free-form text
+    Text(String),
+    /// A list of values
+    List(Vec<Value>),
+    /// A nested list of tags, each with its own fields
+    Tags(Vec<Group>),
+    /// Raw bytes found at `offset` within the data, shown as a hex dump
+    Bytes { offset: usize, data: Vec<u8> },
+}
+
+/// A named field of a header
+#[derive(Clone, Debug, PartialEq)]
+pub struct Field {
+    /// A stable, machine-readable name for the field
+    pub name: &'static str,
+    /// A human-readable name for the field
+    pub label: &'static str,
+    pub value: Value,
+}
+
+/// A titled collection of fields, such as a single Multiboot2 tag
+#[derive(Clone, Debug, PartialEq)]
+pub struct Group {
+    pub title: String,
+    pub fields: Vec<Field>,
+}
+
+impl Field {
+    pub fn new(name: &'static str, label: &'static str, value: Value) -> Field {
+        Field { name, label, value }
+    }
+}
+
+impl Value {
+    /// An offset within the data being parsed
+    pub fn offset(offset: usize) -> Value {
+        Value::Hex {
+            value: offset as u64,
+            digits: 8,
+        }
+    }
+
+    /// A 16-bit value shown in hexadecimal
+    pub fn hex16(value: u16) -> Value {
+        Value::Hex {
+            value: u64::from(value),
+            digits: 4,
+        }
+    }
+
+    /// A 32-bit value shown in hexadecimal
+    pub fn hex32(value: u32) -> Value {
+        Value::Hex {
+            value: u64::from(value),
+            digits: 8,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            Value::Integer(value) => write!(f, "{}", value),
+            Value::Hex { value, digits } => write!(f, "0x{:0width$x}", value, width = digits),
+            Value::Size(value) => write!(f, "{} bytes", value),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Enum { value, name } => write!(f, "{} ({})", name, value),
+            Value::Flags {
+                bits,
+                digits,
+                ref names,
+            } => write!(f, "[{}] (0x{:0width$x})", names.join(", "), bits, width = digits),
+            Value::Text(ref text) => write!(f, "{}", text),
+            Value::List(ref values) => {
+                let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+                write!(f, "[{}]", values.join(", "))
+            }
+            Value::Tags(ref tags) => write!(f, "{} tags", tags.len()),
+            Value::Bytes { ref data, .. } => write!(f, "{} bytes", data.len()),
+        }
+    }
+}
+
+impl Value {
+    /// Convert the value into its JSON representation
+    pub fn to_json(&self) -> serde_json::Value {
+        match *self {
+            Value::Integer(value) |
+            Value::Hex { value, .. } |
+            Value::Size(value) => value.into(),
+            Value::Bool(value) => value.into(),
+            Value::Enum { value, name } => json!({ "value": value, "name": name }),
+            Value::Flags {
+                bits,
+                ref names,
+                ..
+            } => json!({ "bits": bits, "names": names }),
+            Value::Text(ref text) => text.as_str().into(),
+            Value::List(ref values) => values.iter().map(Value::to_json).collect(),
+            Value::Tags(ref tags) => {
+                tags.iter()
+                    .map(|tag| json!({ "title": tag.title, "fields": fields_to_json(&tag.fields) }))
+                    .collect()
+            }
+            Value::Bytes { ref data, .. } => {
+                let hex: Vec<String> = data.iter().map(|b| format!("{:02x}", b)).collect();
+                serde_json::Value::String(hex.concat())
+            }
+        }
+    }
+}
+
+fn fields_to_json(fields: &[Field]) -> serde_json::Value {
+    let map: Map<String, serde_json::Value> = fields
+        .iter()
+        .map(|field| (field.name.to_string(), field.value.to_json()))
+        .collect();
+    serde_json::Value::Object(map)
+}
+
+fn label_width(fields: &[Field]) -> usize {
+    fields
+        .iter()
+        .filter(|field| !matches!(field.value, Value::Tags(_)))
+        .map(|field| field.label.len())
+        .max()
+        .unwrap_or(0)
+}
+
+/// Write `data` as rows of 16 bytes, each with the offset of its first byte and the printable
+/// characters in it
+fn write_hex_dump(f: &mut fmt::Formatter,
+                  indent: usize,
+                  offset: usize,
+                  data: &[u8])
+                  -> Result<(), fmt::Error> {
+    for (row, chunk) in data.chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+        let text: String = chunk.iter()
+            .map(|&b| if b.is_ascii_graphic() || b == b' ' {
+                     b as char
+                 } else {
+                     '.'
+                 })
+            .collect();
+        writeln!(f,
+                 "{:indent$}0x{:08x}: {:47}  |{}|",
+                 "",
+                 offset + row * 16,
+                 hex.join(" "),
+                 text,
+                 indent = indent)?;
+    }
+    Ok(())
+}
+
+fn write_fields(f: &mut fmt::Formatter,
+                indent: usize,
+                width: usize,
+                fields: &[Field])
+                -> Result<(), fmt::Error> {
+    for field in fields {
+        match field.value {
+            Value::Tags(ref tags) => {
+                // Line up the values of every tag in the list, not just within each tag
+                let tag_width = tags.iter()
+                    .map(|tag| label_width(&tag.fields))
+                    .max()
+                    .unwrap_or(0);
+                for tag in tags {
+                    writeln!(f, "{:indent$}{}", "", tag.title, indent = indent)?;
+                    write_fields(f, indent + 2, tag_width, &tag.fields)?;
+                }
+            }
+            ref value => {
+                writeln!(f,
+                         "{:indent$}{:width$} : {}",
+                         "",
+                         field.label,
+                         value,
+                         indent = indent,
+                         width = width)?;
+                if let Value::Bytes { offset, ref data } = *value {
+                    write_hex_dump(f, indent + 2, offset, data)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Render the fields of a header as indented, column-aligned text
+pub fn write_text<T: super::BootInfo + ?Sized>(info: &T,
+                                                f: &mut fmt::Formatter)
+                                                -> Result<(), fmt::Error> {
+    writeln!(f, "{}", info.title())?;
+    let fields = info.fields();
+    write_fields(f, 2, label_width(&fields), &fields)?;
+    for warning in info.warnings() {
+        writeln!(f, "  Warning: {}", warning)?;
+    }
+    Ok(())
+}
+
+/// Render the fields of a header as a JSON object
+pub fn to_json<T: super::BootInfo + ?Sized>(info: &T) -> serde_json::Value {
+    json!({
+        "format": info.format(),
+        "title": info.title(),
+        "fields": fields_to_json(&info.fields()),
+        "warnings": info.warnings(),
+    })
+}
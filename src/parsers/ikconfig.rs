@@ -0,0 +1,120 @@
+//! The kernel configuration that a kernel built with `CONFIG_IKCONFIG` carries.
+//!
+//! The build embeds the gzip-compressed `.config` between the `IKCFG_ST` and `IKCFG_ED` markers,
+//! usually inside the compressed kernel payload.
+
+use super::Rejection;
+use flate2::read::GzDecoder;
+use std::io::Read;
+
+pub const START: &[u8] = b"IKCFG_ST";
+pub const END: &[u8] = b"IKCFG_ED";
+
+/// The largest configuration that is decompressed, to bound memory use on corrupt input
+const MAX_CONFIG_SIZE: u64 = 16 << 20;
+
+/// The first offset of `needle` within `haystack`
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+/// The text of an embedded kernel configuration
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KernelConfig {
+    offset: usize,
+    text: String,
+}
+
+impl KernelConfig {
+    /// Find and decompress the configuration embedded in `data`
+    pub fn find(data: &[u8]) -> Result<KernelConfig, Rejection> {
+        let offset = find_bytes(data, START).ok_or(Rejection::NotPresent)?;
+        let blob = &data[offset + START.len()..];
+        let len = find_bytes(blob, END).ok_or_else(|| {
+                                                       Rejection::invalid(offset,
+                                                                          "IKCFG_ST is not \
+                                                                           followed by IKCFG_ED")
+                                                   })?;
+
+        let mut text = String::new();
+        GzDecoder::new(&blob[..len])
+            .take(MAX_CONFIG_SIZE)
+            .read_to_string(&mut text)
+            .map_err(|e| {
+                         Rejection::invalid(offset,
+                                            format!("failed to decompress the configuration: {}",
+                                                    e))
+                     })?;
+        Ok(KernelConfig { offset, text })
+    }
+
+    /// The offset of the `IKCFG_ST` marker within the data that was searched
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The configuration as it would appear in a `.config` file
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Every option that is set, along with its value
+    pub fn options(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.text
+            .lines()
+            .filter(|line| line.starts_with("CONFIG_"))
+            .filter_map(|line| {
+                            let mut parts = line.splitn(2, '=');
+                            Some((parts.next()?, parts.next()?))
+                        })
+    }
+
+    /// The value of an option, with or without its `CONFIG_` prefix, if it is set
+    pub fn get(&self, option: &str) -> Option<&str> {
+        let option = option.trim_start_matches("CONFIG_");
+        self.options()
+            .find(|&(name, _)| name.trim_start_matches("CONFIG_") == option)
+            .map(|(_, value)| value)
+    }
+
+    /// Whether a boolean or tristate option is built in
+    pub fn enabled(&self, option: &str) -> bool {
+        self.get(option) == Some("y")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parsers::builders::{embedded, CONFIG};
+
+    #[test]
+    fn find_config() {
+        let mut data = vec![0u8; 100];
+        data.extend(embedded(CONFIG));
+        data.extend(vec![0u8; 100]);
+        let config = KernelConfig::find(&data).unwrap();
+        assert_eq!(config.offset(), 100);
+        assert_eq!(config.text(), CONFIG);
+        assert_eq!(config.get("CONFIG_64BIT"), Some("y"));
+        assert_eq!(config.get("LOCALVERSION"), Some("\"-test\""));
+        assert_eq!(config.get("RELOCATABLE"), None);
+        assert!(config.enabled("EFI_STUB"));
+        assert!(!config.enabled("EXT4_FS"));
+        assert_eq!(config.options().count(), 4);
+    }
+
+    #[test]
+    fn reject_missing_end_marker() {
+        let mut data = embedded(CONFIG);
+        let len = data.len();
+        data.truncate(len - END.len());
+        assert_eq!(KernelConfig::find(&data),
+                   Err(Rejection::invalid(0, "IKCFG_ST is not followed by IKCFG_ED")));
+    }
+
+    #[test]
+    fn config_not_present() {
+        assert_eq!(KernelConfig::find(&[0u8; 64]), Err(Rejection::NotPresent));
+    }
+}
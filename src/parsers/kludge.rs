@@ -0,0 +1,170 @@
+//! The address fields of the a.out kludge, which Multiboot kernels that are not ELF use to tell
+//! the boot loader how to load them.
+//!
+//! Multiboot1 headers carry them when `ENTRY_ADDRS_VALID` is set and Multiboot2 headers in an
+//! `Address` tag; both are checked the same way here.
+
+/// The load addresses that a header claims, along with where the header sits in the file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Kludge {
+    /// The offset of the header within the (decompressed) data
+    pub header_offset: usize,
+    pub header_addr: u32,
+    pub load_addr: u32,
+    /// Zero when the rest of the file is loaded
+    pub load_end_addr: u32,
+    /// Zero when there is no BSS
+    pub bss_end_addr: u32,
+    /// The entry point, when the header gives one
+    pub entry_addr: Option<u32>,
+}
+
+impl Kludge {
+    /// The offset in the file at which loading starts, or `None` when the addresses place it
+    /// before the start of the file
+    pub fn load_offset(&self) -> Option<usize> {
+        let before_header = self.header_addr.checked_sub(self.load_addr)? as usize;
+        self.header_offset.checked_sub(before_header)
+    }
+
+    /// The first address past what is loaded from the file, when the header says
+    fn load_end(&self) -> Option<u32> {
+        if self.load_end_addr == 0 {
+            None
+        } else {
+            Some(self.load_end_addr)
+        }
+    }
+
+    /// Every way the addresses break the constraints of the specification
+    pub fn violations(&self) -> Vec<String> {
+        let mut violations = vec![];
+
+        if self.load_addr > self.header_addr {
+            violations.push(format!("load_addr 0x{:08x} is above header_addr 0x{:08x}",
+                                    self.load_addr,
+                                    self.header_addr));
+        } else if self.load_offset().is_none() {
+            violations.push(format!("loading starts 0x{:x} bytes before the header, but the \
+                                     header is only 0x{:x} bytes into the file",
+                                    self.header_addr - self.load_addr,
+                                    self.header_offset));
+        }
+
+        if let Some(load_end) = self.load_end() {
+            if load_end <= self.load_addr {
+                violations.push(format!("load_end_addr 0x{:08x} is not above load_addr \
+                                         0x{:08x}",
+                                        load_end,
+                                        self.load_addr));
+            } else if self.header_addr >= load_end {
+                violations.push(format!("header_addr 0x{:08x} is not below load_end_addr \
+                                         0x{:08x}, so the header is not loaded",
+                                        self.header_addr,
+                                        load_end));
+            }
+            if self.bss_end_addr != 0 && self.bss_end_addr < load_end {
+                violations.push(format!("bss_end_addr 0x{:08x} is below load_end_addr 0x{:08x}",
+                                        self.bss_end_addr,
+                                        load_end));
+            }
+        } else if self.bss_end_addr != 0 && self.bss_end_addr < self.header_addr {
+            // Without load_end_addr the image runs at least past the header
+            violations.push(format!("bss_end_addr 0x{:08x} is below header_addr 0x{:08x}",
+                                    self.bss_end_addr,
+                                    self.header_addr));
+        }
+
+        if let Some(entry) = self.entry_addr {
+            let past_end = self.load_end().is_some_and(|load_end| entry >= load_end);
+            if entry < self.load_addr || past_end {
+                let end = self.load_end()
+                    .map_or_else(|| "the end of the file".to_string(),
+                                 |load_end| format!("0x{:08x}", load_end));
+                violations.push(format!("entry_addr 0x{:08x} is outside the loaded range \
+                                         0x{:08x} to {}",
+                                        entry,
+                                        self.load_addr,
+                                        end));
+            }
+        }
+
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A consistent layout: the header 0x40 bytes into a file that is loaded at 1 MiB
+    fn layout() -> Kludge {
+        Kludge {
+            header_offset: 0x40,
+            header_addr: 0x0010_0040,
+            load_addr: 0x0010_0000,
+            load_end_addr: 0x0010_8000,
+            bss_end_addr: 0x0011_0000,
+            entry_addr: Some(0x0010_0100),
+        }
+    }
+
+    #[test]
+    fn accept_consistent_addresses() {
+        assert_eq!(layout().load_offset(), Some(0));
+        assert!(layout().violations().is_empty());
+
+        let whole_file = Kludge {
+            load_end_addr: 0,
+            bss_end_addr: 0,
+            ..layout()
+        };
+        assert!(whole_file.violations().is_empty());
+    }
+
+    #[test]
+    fn locate_load_start_in_file() {
+        let kludge = Kludge {
+            header_offset: 0x1040,
+            ..layout()
+        };
+        assert_eq!(kludge.load_offset(), Some(0x1000));
+        assert!(kludge.violations().is_empty());
+    }
+
+    #[test]
+    fn report_load_before_start_of_file() {
+        let kludge = Kludge {
+            load_addr: 0x000f_0000,
+            ..layout()
+        };
+        assert_eq!(kludge.load_offset(), None);
+        assert_eq!(kludge.violations().len(), 1);
+    }
+
+    #[test]
+    fn report_each_violation() {
+        let kludge = Kludge {
+            header_addr: 0x0010_8000,
+            load_end_addr: 0x0010_8000,
+            bss_end_addr: 0x0010_4000,
+            entry_addr: Some(0x0020_0000),
+            header_offset: 0x8000,
+            ..layout()
+        };
+        let violations = kludge.violations();
+        assert_eq!(violations.len(), 3, "{:?}", violations);
+        assert!(violations[0].starts_with("header_addr"));
+        assert!(violations[1].starts_with("bss_end_addr"));
+        assert!(violations[2].starts_with("entry_addr"));
+
+        let kludge = Kludge {
+            load_addr: 0x0010_0080,
+            load_end_addr: 0x0010_0080,
+            ..layout()
+        };
+        let violations = kludge.violations();
+        assert!(violations[0].starts_with("load_addr"));
+        assert!(violations[1].starts_with("load_end_addr"));
+    }
+}
@@ -1,30 +1,212 @@
-use bytes::{self, Buf, IntoBuf, LittleEndian};
+use super::elf::Elf;
+use super::ikconfig::KernelConfig;
+use super::pe::{self, Pe};
+use super::{Field, Group, Rejection, TryBuf, Value};
+use bytes::{self, Buf, IntoBuf};
 use std::fmt;
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
+use std::io::Read;
+use utils::{Compression, MAX_FILE_SIZE};
 pub const MAGIC: u32 = 0x53726448;
 
-#[derive(Debug)]
+/// The offset of the setup header within the boot sector
+pub const SETUP_HEADER_OFFSET: usize = 0x1f1;
+
+bitflags! {
+        pub struct LoadFlags: u8 {
+            const LOADED_HIGH   = 0x01;
+            const KASLR_FLAG    = 0x02;
+            const QUIET_FLAG    = 0x20;
+            const KEEP_SEGMENTS = 0x40;
+            const CAN_USE_HEAP  = 0x80;
+            const UNKNOWN_FLAGS = 0x1c;
+        }
+    }
+
+impl LoadFlags {
+    /// The names of the flags that are set
+    pub fn names(&self) -> Vec<&'static str> {
+        let mut flags = vec![];
+
+        if self.contains(LoadFlags::LOADED_HIGH) {
+            flags.push("loaded-high");
+        }
+
+        if self.contains(LoadFlags::KASLR_FLAG) {
+            flags.push("kaslr");
+        }
+
+        if self.contains(LoadFlags::QUIET_FLAG) {
+            flags.push("quiet");
+        }
+
+        if self.contains(LoadFlags::KEEP_SEGMENTS) {
+            flags.push("keep-segments");
+        }
+
+        if self.contains(LoadFlags::CAN_USE_HEAP) {
+            flags.push("can-use-heap");
+        }
+
+        if self.intersects(LoadFlags::UNKNOWN_FLAGS) {
+            flags.push("unknown");
+        }
+
+        flags
+    }
+}
+
+impl fmt::Display for LoadFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "[{}]", self.names().join(", "))
+    }
+}
+
+bitflags! {
+        pub struct XLoadFlags: u16 {
+            const KERNEL_64               = 0x0001;
+            const CAN_BE_LOADED_ABOVE_4G  = 0x0002;
+            const EFI_HANDOVER_32         = 0x0004;
+            const EFI_HANDOVER_64         = 0x0008;
+            const EFI_KEXEC               = 0x0010;
+            const FIVE_LEVEL              = 0x0020;
+            const FIVE_LEVEL_ENABLED      = 0x0040;
+            const UNKNOWN_FLAGS           = 0xff80;
+        }
+    }
+
+impl XLoadFlags {
+    /// The names of the flags that are set
+    pub fn names(&self) -> Vec<&'static str> {
+        let mut flags = vec![];
+
+        if self.contains(XLoadFlags::KERNEL_64) {
+            flags.push("kernel-64");
+        }
+
+        if self.contains(XLoadFlags::CAN_BE_LOADED_ABOVE_4G) {
+            flags.push("can-be-loaded-above-4g");
+        }
+
+        if self.contains(XLoadFlags::EFI_HANDOVER_32) {
+            flags.push("efi-handover-32");
+        }
+
+        if self.contains(XLoadFlags::EFI_HANDOVER_64) {
+            flags.push("efi-handover-64");
+        }
+
+        if self.contains(XLoadFlags::EFI_KEXEC) {
+            flags.push("efi-kexec");
+        }
+
+        if self.contains(XLoadFlags::FIVE_LEVEL) {
+            flags.push("5level");
+        }
+
+        if self.contains(XLoadFlags::FIVE_LEVEL_ENABLED) {
+            flags.push("5level-enabled");
+        }
+
+        if self.intersects(XLoadFlags::UNKNOWN_FLAGS) {
+            flags.push("unknown");
+        }
+
+        flags
+    }
+}
+
+impl fmt::Display for XLoadFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "[{}]", self.names().join(", "))
+    }
+}
+
+/// The offset of `payload_offset` within the setup header
+const PAYLOAD_OFFSET_OFFSET: usize = 0x248;
+
+/// The offset of the `kernel_info_offset` field within the image
+const KERNEL_INFO_OFFSET_OFFSET: usize = 0x268;
+
+/// The magic at the start of the `kernel_info` structure, "LToP" in little-endian order
+pub const KERNEL_INFO_MAGIC: u32 = 0x506f_544c;
+
+/// The size of the fixed part of `kernel_info` that boot protocol 2.15 defines
+const KERNEL_INFO_MIN_SIZE: usize = 0x10;
+
+/// The bit of a `setup_data` type that marks it as pointing at its data indirectly
+const SETUP_INDIRECT: u32 = 1 << 31;
+
+/// The distance from the 32-bit EFI handover entry point to the 64-bit one
+const HANDOVER_64_OFFSET: usize = 0x200;
+
+/// The type of a `.compat` entry that gives the PE entry point for another machine type
+const COMPAT_PE_ENTRY: u8 = 1;
+
+/// The value of `boot_flag` in a valid boot sector
+pub const BOOT_FLAG: u16 = 0xaa55;
+
+/// The opcode of the short jump at the start of the setup code
+const JMP_SHORT: u8 = 0xeb;
+
+/// The segment the BIOS loads the boot sector to, which the old `bootsect.S` starts by using
+const BOOTSEG: [u8; 2] = [0xc0, 0x07];
+
+/// The most protected-mode code a zImage can have, as it is loaded between 0x10000 and 0x90000
+const MAX_ZIMAGE_SIZE: u64 = 0x80000;
+
+/// Whether `data` starts with the code of a boot sector from before boot protocol 2.0, which
+/// either loads `BOOTSEG` into %ax or jumps into it
+fn is_legacy_boot_sector(data: &[u8]) -> bool {
+    match *data {
+        [0xb8, seg0, seg1, ..] if [seg0, seg1] == BOOTSEG => true,
+        [0xea, _, _, seg0, seg1, ..] if [seg0, seg1] == BOOTSEG => true,
+        _ => false,
+    }
+}
+
+#[derive(Debug, Default)]
 pub struct Header {
     setup_sects: u8,
+    root_flags: u16,
     syssize: u32,
-    header: u32,
+    ram_size: u16,
+    vid_mode: u16,
+    root_dev: u16,
+    boot_flag: u16,
+    jump: Option<u16>,
+    header: Option<u32>,
     version_major: u8,
     version_minor: u8,
     realmode_swtch: Option<u32>,
+    start_sys_seg: Option<u16>,
     kernel_version: Option<CString>,
-    load_flags: Option<u8>,
+    type_of_loader: Option<u8>,
+    load_flags: Option<LoadFlags>,
+    setup_move_size: Option<u16>,
     code32_start: Option<u32>,
+    ramdisk_image: Option<u32>,
+    ramdisk_size: Option<u32>,
+    bootsect_kludge: Option<u32>,
+    heap_end_ptr: Option<u16>,
+    ext_loader_ver: Option<u8>,
+    ext_loader_type: Option<u8>,
+    cmd_line_ptr: Option<u32>,
     initrd_addr_max: Option<u32>,
     kernel_alignment: Option<u32>,
     relocatable_kernel: Option<bool>,
     min_alignment: Option<u32>,
-    xloadflags: Option<u16>,
+    xloadflags: Option<XLoadFlags>,
     cmdline_size: Option<u32>,
+    hardware_subarch: Option<u32>,
+    hardware_subarch_data: Option<u64>,
     payload_offset: Option<u32>,
     payload_length: Option<u32>,
+    setup_data: Option<u64>,
     pref_address: Option<u64>,
     init_size: Option<u32>,
     handover_offset: Option<u32>,
+    kernel_info_offset: Option<u32>,
 }
 
 fn valid<V>(version: u16, allowed: (u8, u8), value: V) -> Option<V> {
@@ -38,56 +220,384 @@ fn valid<V>(version: u16, allowed: (u8, u8), value: V) -> Option<V> {
     }
 }
 
-impl super::BootInfo for Header {}
+/// The name of a `vid_mode` value, as described in Documentation/fb/vga.rst
+fn vid_mode_name(mode: u16) -> &'static str {
+    match mode {
+        0xffff => "normal",
+        0xfffe => "extended",
+        0xfffd => "ask",
+        0x0f00 => "80x25",
+        0x0f01 => "80x50",
+        0x0f02 => "80x43",
+        0x0f03 => "80x28",
+        0x0f04 => "current",
+        0x0f05 => "80x30",
+        0x0f06 => "80x34",
+        0x0f07 => "80x60",
+        0x0f08 => "graphics",
+        0x0000..=0x00ff => "menu item",
+        0x0100..=0x017f => "BIOS mode",
+        0x0200..=0x08ff => "VESA mode",
+        0x0900..=0x09ff => "Video7 mode",
+        0x1000..=0x7fff => "resolution",
+        _ => "unknown",
+    }
+}
+
+/// The name of the boot loader in the high nibble of `type_of_loader`
+fn loader_name(type_of_loader: u8) -> &'static str {
+    match type_of_loader >> 4 {
+        0x0 => "LILO",
+        0x1 => "Loadlin",
+        0x2 => "bootsect-loader",
+        0x3 => "Syslinux",
+        0x4 => "Etherboot/gPXE/iPXE",
+        0x5 => "ELILO",
+        0x7 => "GRUB",
+        0x8 => "U-Boot",
+        0x9 => "Xen",
+        0xa => "Gujin",
+        0xb => "Qemu",
+        0xc => "Arcturus Networks uCbootloader",
+        0xd => "kexec-tools",
+        0xe => "extended",
+        0xf if type_of_loader == 0xff => "undefined",
+        0xf => "special",
+        _ => "unknown",
+    }
+}
+
+/// The name of a `hardware_subarch` value
+fn subarch_name(subarch: u32) -> &'static str {
+    match subarch {
+        0 => "PC",
+        1 => "lguest",
+        2 => "Xen",
+        3 => "Intel MID",
+        4 => "CE4100",
+        _ => "unknown",
+    }
+}
+
+impl super::BootInfo for Header {
+    fn format(&self) -> &'static str {
+        "linux"
+    }
+
+    fn title(&self) -> &'static str {
+        "Linux Boot Protocol"
+    }
+
+    fn fields(&self) -> Vec<Field> {
+        let mut fields = vec![Field::new("offset", "Offset", Value::offset(self.offset()))];
+
+        if let Some(ref version) = self.kernel_version {
+            fields.push(Field::new("kernel_version",
+                                   "Kernel Version",
+                                   Value::Text(version.to_string_lossy().into_owned())));
+        }
+        if let Some(header) = self.header {
+            fields.push(Field::new("header", "Header", Value::hex32(header)));
+        }
+        fields.push(Field::new("version", "Version", Value::Text(self.version())));
+        fields.push(Field::new("setup_sects",
+                               "Setup Sectors",
+                               Value::Integer(if self.setup_sects == 0 {
+                                                  4
+                                              } else {
+                                                  u64::from(self.setup_sects)
+                                              })));
+        fields.push(Field::new("root_flags", "Root Read-only", Value::Bool(self.root_flags != 0)));
+        fields.push(Field::new("syssize",
+                               "PM Code Size",
+                               Value::Size(u64::from(self.syssize) * 16)));
+        fields.push(Field::new("ram_size", "RAM Disk Flags", Value::hex16(self.ram_size)));
+        fields.push(Field::new("vid_mode",
+                               "Video Mode",
+                               Value::Enum {
+                                   value: u64::from(self.vid_mode),
+                                   name: vid_mode_name(self.vid_mode),
+                               }));
+        fields.push(Field::new("root_dev",
+                               "Root Device",
+                               Value::Text(format!("{}:{}",
+                                                   self.root_dev >> 8,
+                                                   self.root_dev & 0xff))));
+        fields.push(Field::new("boot_flag", "Boot Flag", Value::hex16(self.boot_flag)));
+        if let Some(jump_target) = self.jump_target() {
+            fields.push(Field::new("jump", "Jump Target", Value::offset(jump_target)));
+        }
+        if let Some(realmode_swtch) = self.realmode_swtch {
+            fields.push(Field::new("realmode_swtch",
+                                   "Realmode Switch",
+                                   Value::hex32(realmode_swtch)));
+        }
+        if let Some(start_sys_seg) = self.start_sys_seg {
+            fields.push(Field::new("start_sys_seg",
+                                   "Start Sys Segment",
+                                   Value::hex16(start_sys_seg)));
+        }
+        if let Some(type_of_loader) = self.type_of_loader {
+            fields.push(Field::new("type_of_loader",
+                                   "Type of Loader",
+                                   Value::Enum {
+                                       value: u64::from(type_of_loader),
+                                       name: if type_of_loader == 0 {
+                                           "not set"
+                                       } else {
+                                           loader_name(type_of_loader)
+                                       },
+                                   }));
+        }
+        if let Some(load_flags) = self.load_flags {
+            fields.push(Field::new("load_flags",
+                                   "Load Flags",
+                                   Value::Flags {
+                                       bits: u64::from(load_flags.bits()),
+                                       digits: 2,
+                                       names: load_flags.names(),
+                                   }));
+        }
+        if let Some(setup_move_size) = self.setup_move_size {
+            fields.push(Field::new("setup_move_size",
+                                   "Setup Move Size",
+                                   Value::Size(u64::from(setup_move_size))));
+        }
+        if let Some(code32_start) = self.code32_start {
+            fields.push(Field::new("code32_start", "Code32 Start", Value::hex32(code32_start)));
+        }
+        if let Some(ramdisk_image) = self.ramdisk_image {
+            fields.push(Field::new("ramdisk_image", "Ramdisk Image", Value::hex32(ramdisk_image)));
+        }
+        if let Some(ramdisk_size) = self.ramdisk_size {
+            fields.push(Field::new("ramdisk_size",
+                                   "Ramdisk Size",
+                                   Value::Size(u64::from(ramdisk_size))));
+        }
+        if let Some(bootsect_kludge) = self.bootsect_kludge {
+            fields.push(Field::new("bootsect_kludge",
+                                   "Bootsect Kludge",
+                                   Value::hex32(bootsect_kludge)));
+        }
+        if let Some(heap_end_ptr) = self.heap_end_ptr {
+            fields.push(Field::new("heap_end_ptr", "Heap End", Value::hex16(heap_end_ptr)));
+        }
+        if let Some(ext_loader_ver) = self.ext_loader_ver {
+            fields.push(Field::new("ext_loader_ver",
+                                   "Ext Loader Version",
+                                   Value::Integer(u64::from(ext_loader_ver))));
+        }
+        if let Some(ext_loader_type) = self.ext_loader_type {
+            fields.push(Field::new("ext_loader_type",
+                                   "Ext Loader Type",
+                                   Value::Integer(u64::from(ext_loader_type))));
+        }
+        if let Some(cmd_line_ptr) = self.cmd_line_ptr {
+            fields.push(Field::new("cmd_line_ptr", "Cmdline Pointer", Value::hex32(cmd_line_ptr)));
+        }
+        if let Some(initrd_addr_max) = self.initrd_addr_max {
+            fields.push(Field::new("initrd_addr_max",
+                                   "Initrd Addr Max",
+                                   Value::hex32(initrd_addr_max)));
+        }
+        if let Some(kernel_alignment) = self.kernel_alignment {
+            fields.push(Field::new("kernel_alignment",
+                                   "Kernel Alignment",
+                                   Value::hex32(kernel_alignment)));
+        }
+        if let Some(min_alignment) = self.min_alignment {
+            fields.push(Field::new("min_alignment",
+                                   "Min. Kernel Alignment",
+                                   Value::hex32(min_alignment)));
+        }
+        if let Some(relocatable_kernel) = self.relocatable_kernel {
+            fields.push(Field::new("relocatable_kernel",
+                                   "Relocatable?",
+                                   Value::Bool(relocatable_kernel)));
+        }
+        if let Some(xloadflags) = self.xloadflags {
+            fields.push(Field::new("xloadflags",
+                                   "XLoad Flags",
+                                   Value::Flags {
+                                       bits: u64::from(xloadflags.bits()),
+                                       digits: 4,
+                                       names: xloadflags.names(),
+                                   }));
+        }
+        if let Some(cmdline_size) = self.cmdline_size {
+            fields.push(Field::new("cmdline_size",
+                                   "Max Cmdline Size",
+                                   Value::Size(u64::from(cmdline_size))));
+        }
+        if let Some(hardware_subarch) = self.hardware_subarch {
+            fields.push(Field::new("hardware_subarch",
+                                   "Hardware Subarch",
+                                   Value::Enum {
+                                       value: u64::from(hardware_subarch),
+                                       name: subarch_name(hardware_subarch),
+                                   }));
+        }
+        if let Some(hardware_subarch_data) = self.hardware_subarch_data {
+            fields.push(Field::new("hardware_subarch_data",
+                                   "Subarch Data",
+                                   Value::Hex {
+                                       value: hardware_subarch_data,
+                                       digits: 16,
+                                   }));
+        }
+        if let Some(payload_offset) = self.payload_offset {
+            fields.push(Field::new("payload_offset",
+                                   "Payload Offset",
+                                   Value::hex32(payload_offset)));
+        }
+        if let Some(payload_length) = self.payload_length {
+            fields.push(Field::new("payload_length",
+                                   "Payload length",
+                                   Value::Size(u64::from(payload_length))));
+        }
+        if let Some(setup_data) = self.setup_data {
+            fields.push(Field::new("setup_data",
+                                   "Setup Data",
+                                   Value::Hex {
+                                       value: setup_data,
+                                       digits: 16,
+                                   }));
+        }
+        if let Some(init_size) = self.init_size {
+            fields.push(Field::new("init_size", "init size", Value::Size(u64::from(init_size))));
+        }
+        if let Some(pref_address) = self.pref_address {
+            fields.push(Field::new("pref_address",
+                                   "Preferred load address",
+                                   Value::Hex {
+                                       value: pref_address,
+                                       digits: 16,
+                                   }));
+        }
+        if let Some(handover_offset) = self.handover_offset {
+            fields.push(Field::new("handover_offset",
+                                   "EFI Handover Offset",
+                                   Value::hex32(handover_offset)));
+        }
+        if let Some(kernel_info_offset) = self.kernel_info_offset {
+            fields.push(Field::new("kernel_info_offset",
+                                   "Kernel Info Offset",
+                                   Value::hex32(kernel_info_offset)));
+        }
+
+        fields
+    }
+
+    fn warnings(&self) -> Vec<String> {
+        let mut warnings = vec![];
+        if let Some(load_flags) = self.load_flags {
+            let unknown = load_flags & LoadFlags::UNKNOWN_FLAGS;
+            if !unknown.is_empty() {
+                warnings.push(format!("unknown loadflags bits 0x{:02x}", unknown.bits()));
+            }
+        }
+        if let Some(xloadflags) = self.xloadflags {
+            let unknown = xloadflags & XLoadFlags::UNKNOWN_FLAGS;
+            if !unknown.is_empty() {
+                warnings.push(format!("unknown xloadflags bits 0x{:04x}", unknown.bits()));
+            }
+        }
+        warnings
+    }
+}
 
 impl Header {
-    pub fn parse(buf: bytes::Bytes) -> Option<Box<super::BootInfo>> {
+    pub fn parse(buf: bytes::Bytes) -> Result<Header, Rejection> {
         let mut version_buf = buf.clone().into_buf();
+        let mut magic_buf = buf.clone().into_buf();
         let mut buf = buf.into_buf();
 
-        if buf.remaining() < 0x1f2 {
-            return None;
+        if magic_buf.remaining() < 0x200 {
+            return Err(Rejection::NotPresent);
         }
-
-        buf.advance(0x1f1);
-        let setup_sects = buf.get_u8();
-
-        if buf.remaining() < ((setup_sects as usize * 512) - 0x1f2) {
-            return None;
+        // Boot sectors from before protocol 2.0 have no magic, only their distinctive first
+        // instruction
+        let legacy = magic_buf.remaining() < 0x206 || {
+            magic_buf.advance(0x202);
+            magic_buf.get_u32_le() != MAGIC
+        };
+        if legacy && !is_legacy_boot_sector(buf.get_ref()) {
+            return Err(Rejection::NotPresent);
         }
 
-        // move past deprecated root_flags
-        buf.advance(2);
-        let syssize = buf.get_u32::<LittleEndian>();
+        buf.advance(SETUP_HEADER_OFFSET);
+        let setup_sects = buf.try_get_u8()?;
+
+        let root_flags = buf.try_get_u16_le()?;
+        let syssize = buf.try_get_u32_le()?;
+        let ram_size = buf.try_get_u16_le()?;
+        let vid_mode = buf.try_get_u16_le()?;
+        let root_dev = buf.try_get_u16_le()?;
 
-        // move past ram_size, vid_mode, root_dev, and boot_flag
-        buf.advance(8);
+        let boot_flag = buf.try_get_u16_le()?;
+        if boot_flag != BOOT_FLAG {
+            return Err(Rejection::invalid(0x1fe,
+                                          format!("boot_flag is 0x{:04x} instead of 0x{:04x}",
+                                                  boot_flag,
+                                                  BOOT_FLAG)));
+        }
 
-        // move past jump
-        buf.advance(2);
-        let header = buf.get_u32::<LittleEndian>();
+        // The rest of the header arrived with protocol 2.0, and syssize was only 16 bits until
+        // protocol 2.04 replaced swap_dev with its upper half
+        if legacy {
+            let syssize = syssize & 0xffff;
+            if u64::from(syssize) * 16 > MAX_ZIMAGE_SIZE {
+                return Err(Rejection::invalid(0x1f4,
+                                              format!("{} bytes of protected-mode code do not fit \
+                                                       below 0x90000",
+                                                      u64::from(syssize) * 16)));
+            }
+            return Ok(Header {
+                          setup_sects,
+                          root_flags,
+                          syssize,
+                          ram_size,
+                          vid_mode,
+                          root_dev,
+                          boot_flag,
+                          ..Header::default()
+                      });
+        }
 
-        // Explicitly not dealing with old boot protocols right now
-        if header != MAGIC {
-            return None;
+        // The setup code starts with a short jump over the rest of the header
+        let jump = buf.try_get_u16_le()?;
+        if jump as u8 != JMP_SHORT {
+            return Err(Rejection::invalid(0x200,
+                                          format!("jump instruction 0x{:04x} is not a short jump",
+                                                  jump)));
+        }
+        let target = 0x202 + (jump >> 8) as i8 as isize;
+        let setup_len = (usize::from(if setup_sects == 0 { 4 } else { setup_sects }) + 1) * 512;
+        if target < 0x206 || target as usize >= setup_len {
+            return Err(Rejection::invalid(0x200,
+                                          format!("jump to 0x{:x} lands outside of the setup \
+                                                   code",
+                                                  target)));
         }
 
-        let version = buf.get_u16::<LittleEndian>();
+        let header = buf.try_get_u32_le()?;
+
+        let version = buf.try_get_u16_le()?;
         let major = version >> 8;
         let minor = version & 0xff;
+        let syssize = if valid(version, (2, 4), ()).is_some() {
+            syssize
+        } else {
+            syssize & 0xffff
+        };
 
-        // Require versions at least 2.0 or newer
-        if major < 2 {
-            return None;
-        }
-
-        let realmode_swtch = buf.get_u32::<LittleEndian>();
-        // move past obsolete start_sys_seg
-        buf.advance(2);
+        let realmode_swtch = buf.try_get_u32_le()?;
+        let start_sys_seg = buf.try_get_u16_le()?;
         let kernel_version = {
-            let version = buf.get_u16::<LittleEndian>();
-            if version != 0 && version < (0x200 * setup_sects as u16) {
-                version_buf.advance(version as usize + 0x200);
+            let version = valid(version, (2, 0), buf.try_get_u16_le()? as usize).unwrap_or(0);
+            if version != 0 && version < (0x200 * setup_sects as usize) &&
+               version + 0x200 < version_buf.remaining() {
+                version_buf.advance(version + 0x200);
                 let s = unsafe {
                     CString::from_vec_unchecked(version_buf
                                                     .iter()
@@ -100,173 +610,1498 @@ impl Header {
             }
         };
 
-        // move past write-only type_of_loader
-        buf.advance(1);
-        let load_flags = buf.get_u8();
-        // move past obsolete setup_move_size
-        buf.advance(2);
-        let code32_start = buf.get_u32::<LittleEndian>();
-        // move past write-only ramdisk_image, ramdisk_size
-        buf.advance(8);
-        // move past obsolete bootsect_kludge
-        buf.advance(4);
-        // move past write-only heap_end_ptr, ext_loader_ver, ext_loader_type, cmdline_ptr
-        buf.advance(8);
-
-        let initrd_addr_max = buf.get_u32::<LittleEndian>();
-        let kernel_alignment = buf.get_u32::<LittleEndian>();
-        let relocatable_kernel = buf.get_u8() != 0;
-        let min_alignment = 1 << buf.get_u8();
-        let xloadflags = buf.get_u16::<LittleEndian>();
-        let cmdline_size = buf.get_u32::<LittleEndian>();
-
-        // move past write-only hardware_subarch
-        buf.advance(4);
-
-        let payload_offset = buf.get_u32::<LittleEndian>();
-        let payload_length = buf.get_u32::<LittleEndian>();
-
-        // move past write-only setup_data
-        buf.advance(8);
+        let type_of_loader = buf.try_get_u8()?;
+        let load_flags = buf.try_get_u8()?;
+        let setup_move_size = buf.try_get_u16_le()?;
+        let code32_start = buf.try_get_u32_le()?;
+        let ramdisk_image = buf.try_get_u32_le()?;
+        let ramdisk_size = buf.try_get_u32_le()?;
+        let bootsect_kludge = buf.try_get_u32_le()?;
+        let heap_end_ptr = buf.try_get_u16_le()?;
+        let ext_loader_ver = buf.try_get_u8()?;
+        let ext_loader_type = buf.try_get_u8()?;
+        let cmd_line_ptr = buf.try_get_u32_le()?;
+
+        let initrd_addr_max = buf.try_get_u32_le()?;
+        let kernel_alignment = buf.try_get_u32_le()?;
+        let relocatable_kernel = buf.try_get_u8()? != 0;
+        let min_alignment = buf.try_get_u8()?;
+        let xloadflags = buf.try_get_u16_le()?;
+        let cmdline_size = buf.try_get_u32_le()?;
+        let hardware_subarch = buf.try_get_u32_le()?;
+        let hardware_subarch_data = buf.try_get_u64_le()?;
+
+        let payload_offset = buf.try_get_u32_le()?;
+        let payload_length = buf.try_get_u32_le()?;
+        let setup_data = buf.try_get_u64_le()?;
+
+        // A shift this large cannot be a real alignment, so the header is corrupt
+        let min_alignment = match valid(version, (2, 10), min_alignment) {
+            Some(shift) => {
+                Some(1u32
+                         .checked_shl(u32::from(shift))
+                         .ok_or_else(|| {
+                                         Rejection::invalid(0x235,
+                                                            format!("min_alignment of 2^{} \
+                                                                     does not fit in 32 bits",
+                                                                    shift))
+                                     })?)
+            }
+            None => None,
+        };
 
         let pref_address = {
-            let value = buf.get_u64::<LittleEndian>();
+            let value = buf.try_get_u64_le()?;
             if value != 0 { Some(value) } else { None }
         };
-        let init_size = buf.get_u32::<LittleEndian>();
-        let handover_offset = buf.get_u32::<LittleEndian>();
+        let init_size = buf.try_get_u32_le()?;
+        let handover_offset = buf.try_get_u32_le()?;
+        // Older versions put the start of the setup code here, so only read it when it exists
+        let kernel_info_offset = match valid(version, (2, 15), ()) {
+            Some(()) => Some(buf.try_get_u32_le()?),
+            None => None,
+        };
 
         let header = Header {
-            setup_sects: setup_sects,
-            syssize: syssize,
-            header: header,
+            setup_sects,
+            root_flags,
+            syssize,
+            ram_size,
+            vid_mode,
+            root_dev,
+            boot_flag,
+            jump: Some(jump),
+            header: Some(header),
             version_major: major as u8,
             version_minor: minor as u8,
             realmode_swtch: valid(version, (2, 0), realmode_swtch),
-            kernel_version: kernel_version,
-            load_flags: valid(version, (2, 0), load_flags),
+            start_sys_seg: valid(version, (2, 0), start_sys_seg),
+            kernel_version,
+            type_of_loader: valid(version, (2, 0), type_of_loader),
+            load_flags: valid(version, (2, 0), LoadFlags::from_bits_truncate(load_flags)),
+            setup_move_size: valid(version, (2, 0), setup_move_size),
             code32_start: valid(version, (2, 0), code32_start),
+            ramdisk_image: valid(version, (2, 0), ramdisk_image),
+            ramdisk_size: valid(version, (2, 0), ramdisk_size),
+            bootsect_kludge: valid(version, (2, 0), bootsect_kludge),
+            heap_end_ptr: valid(version, (2, 1), heap_end_ptr),
+            ext_loader_ver: valid(version, (2, 2), ext_loader_ver),
+            ext_loader_type: valid(version, (2, 2), ext_loader_type),
+            cmd_line_ptr: valid(version, (2, 2), cmd_line_ptr),
             initrd_addr_max: valid(version, (2, 3), initrd_addr_max),
             kernel_alignment: valid(version, (2, 5), kernel_alignment),
             relocatable_kernel: valid(version, (2, 5), relocatable_kernel),
-            min_alignment: valid(version, (2, 10), min_alignment),
-            xloadflags: valid(version, (2, 12), xloadflags),
+            min_alignment,
+            xloadflags: valid(version, (2, 12), XLoadFlags::from_bits_truncate(xloadflags)),
             cmdline_size: valid(version, (2, 6), cmdline_size),
+            hardware_subarch: valid(version, (2, 7), hardware_subarch),
+            hardware_subarch_data: valid(version, (2, 7), hardware_subarch_data),
             payload_offset: valid(version, (2, 8), payload_offset),
             payload_length: valid(version, (2, 8), payload_length),
+            setup_data: valid(version, (2, 9), setup_data),
             pref_address: pref_address.and_then(|addr| valid(version, (2, 10), addr)),
             init_size: valid(version, (2, 10), init_size),
             handover_offset: valid(version, (2, 11), handover_offset),
+            kernel_info_offset,
         };
 
-        Some(Box::new(header) as Box<super::BootInfo>)
+        Ok(header)
     }
-}
 
-impl fmt::Display for Header {
-    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        write!(f, "Linux Boot Protocol\n")?;
-        if let Some(ref version) = self.kernel_version {
-            write!(f, "  Kernel Version: {}\n", version.to_string_lossy())?;
-        }
-        write!(f, "  Header: 0x{:.08x}\n", self.header)?;
-        write!(f,
-               "  Version: {}.{}\n",
-               self.version_major,
-               self.version_minor)?;
-        write!(f,
-               "  Setup Sectors: {}\n",
-               if self.setup_sects == 0 {
-                   4
-               } else {
-                   self.setup_sects
-               })?;
-        write!(f, "  PM Code Size: {} bytes\n", (self.syssize * 16))?;
-        if let Some(realmode_swtch) = self.realmode_swtch {
-            write!(f, "  Realmode Switch: 0x{:.08x}\n", realmode_swtch)?;
-        }
-        if let Some(load_flags) = self.load_flags {
-            write!(f,
-                   "  Loaded: {}\n",
-                   if load_flags & 1 == 1 { "HIGH" } else { "LOW" })?;
-        }
-        if let Some(code32_start) = self.code32_start {
-            write!(f, "  Code32 Start: 0x{:.08x}\n", code32_start)?;
-        }
-        if let Some(initrd_addr_max) = self.initrd_addr_max {
-            write!(f, "  Initrd Addr Max: 0x{:.08x}\n", initrd_addr_max)?;
-        }
-        if let Some(kernel_alignment) = self.kernel_alignment {
-            write!(f, "  Kernel Alignment: 0x{:.08x}\n", kernel_alignment)?;
-        }
-        if let Some(min_alignment) = self.min_alignment {
-            write!(f, "  Min. Kernel Alignment: 0x{:.08x}\n", min_alignment)?;
-        }
-        if let Some(relocatable_kernel) = self.relocatable_kernel {
-            write!(f, "  Relocatable?: {}\n", relocatable_kernel)?;
-        }
-        if let Some(xloadflags) = self.xloadflags {
-            write!(f, "  xloadflags: 0x{:.04x}\n", xloadflags)?;
-        }
-        if let Some(cmdline_size) = self.cmdline_size {
-            write!(f, "  Max Cmdline Size: {} bytes\n", cmdline_size)?;
-        }
-        if let Some(payload_offset) = self.payload_offset {
-            write!(f, "  Payload Offset: 0x{:.08x}\n", payload_offset)?;
-        }
-        if let Some(payload_length) = self.payload_length {
-            write!(f, "  Payload length: {} bytes\n", payload_length)?;
-        }
-        if let Some(init_size) = self.init_size {
-            write!(f, "  init size: {} bytes\n", init_size)?;
-        }
-        if let Some(ref address) = self.pref_address {
-            write!(f, "  Preferred load address: 0x{:.016x}\n", address)?;
+    /// The offset of the setup header within the (decompressed) data
+    pub fn offset(&self) -> usize {
+        SETUP_HEADER_OFFSET
+    }
+
+    /// The offset of the protected-mode code within the image, right after the setup sectors
+    pub fn protected_mode_offset(&self) -> usize {
+        let setup_sects = if self.setup_sects == 0 {
+            4
+        } else {
+            usize::from(self.setup_sects)
+        };
+        (setup_sects + 1) * 512
+    }
+
+    /// Split the image into its real-mode setup sectors, including the boot sector, and its
+    /// protected-mode kernel
+    pub fn split<'a>(&self, image: &'a [u8]) -> Option<(&'a [u8], &'a [u8])> {
+        let offset = self.protected_mode_offset();
+        if offset <= image.len() {
+            Some(image.split_at(offset))
+        } else {
+            None
         }
-        if let Some(handover_offset) = self.handover_offset {
-            write!(f, "  EFI Handover Offset: 0x{:.08x}\n", handover_offset)?;
+    }
+
+    /// The number of 512-byte setup sectors, where 0 means 4
+    pub fn setup_sects(&self) -> u8 {
+        self.setup_sects
+    }
+
+    /// Whether the root filesystem is mounted read-only
+    pub fn root_flags(&self) -> u16 {
+        self.root_flags
+    }
+
+    /// The size of the protected-mode code in 16-byte paragraphs
+    pub fn syssize(&self) -> u32 {
+        self.syssize
+    }
+
+    /// The obsolete RAM disk flags
+    pub fn ram_size(&self) -> u16 {
+        self.ram_size
+    }
+
+    /// The video mode to switch to during setup
+    pub fn vid_mode(&self) -> u16 {
+        self.vid_mode
+    }
+
+    /// The default root device, as its major number in the high byte and minor number in the
+    /// low byte
+    pub fn root_dev(&self) -> u16 {
+        self.root_dev
+    }
+
+    /// The boot sector signature, which is always 0xaa55
+    pub fn boot_flag(&self) -> u16 {
+        self.boot_flag
+    }
+
+    /// The short jump instruction at the start of the setup code, which only images with the
+    /// "HdrS" magic have
+    pub fn jump(&self) -> Option<u16> {
+        self.jump
+    }
+
+    /// The offset that the jump at the start of the setup code lands on
+    pub fn jump_target(&self) -> Option<usize> {
+        self.jump.map(|jump| (0x202 + (jump >> 8) as i8 as isize) as usize)
+    }
+
+    /// The magic value that identified the header, which is missing from legacy boot sectors
+    pub fn header(&self) -> Option<u32> {
+        self.header
+    }
+
+    /// Whether the image is a legacy boot sector from before boot protocol 2.0
+    pub fn legacy(&self) -> bool {
+        self.header.is_none()
+    }
+
+    /// The major version of the boot protocol, which is 0 for a legacy boot sector
+    pub fn version_major(&self) -> u8 {
+        self.version_major
+    }
+
+    /// The minor version of the boot protocol
+    pub fn version_minor(&self) -> u8 {
+        self.version_minor
+    }
+
+    /// The boot protocol version as text
+    pub fn version(&self) -> String {
+        if self.legacy() {
+            "pre-2.0".into()
+        } else {
+            format!("{}.{}", self.version_major, self.version_minor)
         }
-        Ok(())
     }
-}
 
-pub fn register(descs: &mut Vec<super::Descriptor>) {
-    descs.push(super::Descriptor {
-                   name: "linux",
-                   max_range: 32768,
-                   parser: Header::parse,
-               })
-}
+    /// The boot loader hook for switching to real mode
+    pub fn realmode_swtch(&self) -> Option<u32> {
+        self.realmode_swtch
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::io;
-    use utils;
-    const MULTIBOOT1: &[u8; 40000] = include_bytes!("../../test-data/multiboot1");
-    const MULTIBOOT2: &[u8; 40000] = include_bytes!("../../test-data/multiboot2");
-    const LINUXBOOT: &[u8; 40000] = include_bytes!("../../test-data/linuxboot");
+    /// The obsolete load segment of the protected-mode code
+    pub fn start_sys_seg(&self) -> Option<u16> {
+        self.start_sys_seg
+    }
 
-    #[test]
-    #[should_panic]
-    fn parse_invalid_multiboot1() {
-        let cursor = io::Cursor::new(MULTIBOOT1.as_ref());
-        let bytes = utils::header_bytes(cursor, 8192).unwrap();
-        Header::parse(bytes).unwrap();
+    /// The boot loader that loaded the kernel, where 0 means that none has
+    pub fn type_of_loader(&self) -> Option<u8> {
+        self.type_of_loader
     }
 
-    #[test]
-    #[should_panic]
-    fn parse_invalid_multiboot2() {
-        let cursor = io::Cursor::new(MULTIBOOT2.as_ref());
-        let bytes = utils::header_bytes(cursor, 32768).unwrap();
-        Header::parse(bytes).unwrap();
+    /// The human-readable kernel version string
+    pub fn kernel_version(&self) -> Option<&CStr> {
+        self.kernel_version.as_deref()
     }
 
-    #[test]
-    fn parse_valid_linuxboot() {
-        let cursor = io::Cursor::new(LINUXBOOT.as_ref());
-        let bytes = utils::header_bytes(cursor, 32768).unwrap();
-        Header::parse(bytes).unwrap();
+    /// The boot protocol option flags
+    pub fn load_flags(&self) -> Option<LoadFlags> {
+        self.load_flags
+    }
+
+    /// The number of bytes to move when the setup code is not loaded at 0x90000
+    pub fn setup_move_size(&self) -> Option<u16> {
+        self.setup_move_size
+    }
+
+    /// The address to jump to in protected mode
+    pub fn code32_start(&self) -> Option<u32> {
+        self.code32_start
+    }
+
+    /// The address the boot loader placed the initrd at
+    pub fn ramdisk_image(&self) -> Option<u32> {
+        self.ramdisk_image
+    }
+
+    /// The size of the initrd the boot loader loaded
+    pub fn ramdisk_size(&self) -> Option<u32> {
+        self.ramdisk_size
+    }
+
+    /// The obsolete boot sector hook
+    pub fn bootsect_kludge(&self) -> Option<u32> {
+        self.bootsect_kludge
+    }
+
+    /// The end of the setup stack and heap, relative to the start of the setup code
+    pub fn heap_end_ptr(&self) -> Option<u16> {
+        self.heap_end_ptr
+    }
+
+    /// The extension of the boot loader version in `type_of_loader`
+    pub fn ext_loader_ver(&self) -> Option<u8> {
+        self.ext_loader_ver
+    }
+
+    /// The extension of the boot loader type in `type_of_loader`
+    pub fn ext_loader_type(&self) -> Option<u8> {
+        self.ext_loader_type
+    }
+
+    /// The address the boot loader placed the kernel command line at
+    pub fn cmd_line_ptr(&self) -> Option<u32> {
+        self.cmd_line_ptr
+    }
+
+    /// The highest legal address for the initrd
+    pub fn initrd_addr_max(&self) -> Option<u32> {
+        self.initrd_addr_max
+    }
+
+    /// The physical alignment required for a relocatable kernel
+    pub fn kernel_alignment(&self) -> Option<u32> {
+        self.kernel_alignment
+    }
+
+    /// Whether the protected-mode code can be loaded anywhere
+    pub fn relocatable_kernel(&self) -> Option<bool> {
+        self.relocatable_kernel
+    }
+
+    /// The minimum alignment required for a relocatable kernel
+    pub fn min_alignment(&self) -> Option<u32> {
+        self.min_alignment
+    }
+
+    /// The extended boot protocol option flags
+    pub fn xloadflags(&self) -> Option<XLoadFlags> {
+        self.xloadflags
+    }
+
+    /// The maximum size of the kernel command line
+    pub fn cmdline_size(&self) -> Option<u32> {
+        self.cmdline_size
+    }
+
+    /// The hardware platform the kernel is running on
+    pub fn hardware_subarch(&self) -> Option<u32> {
+        self.hardware_subarch
+    }
+
+    /// Data specific to `hardware_subarch`
+    pub fn hardware_subarch_data(&self) -> Option<u64> {
+        self.hardware_subarch_data
+    }
+
+    /// The offset of the compressed kernel payload from the protected-mode code
+    pub fn payload_offset(&self) -> Option<u32> {
+        self.payload_offset
+    }
+
+    /// The length of the compressed kernel payload
+    pub fn payload_length(&self) -> Option<u32> {
+        self.payload_length
+    }
+
+    /// The physical address of the first `setup_data` entry, set by the boot loader
+    pub fn setup_data(&self) -> Option<u64> {
+        self.setup_data
+    }
+
+    /// The preferred load address for a relocatable kernel
+    pub fn pref_address(&self) -> Option<u64> {
+        self.pref_address
+    }
+
+    /// The linear memory required during initialization
+    pub fn init_size(&self) -> Option<u32> {
+        self.init_size
+    }
+
+    /// The offset of the EFI handover protocol entry point
+    pub fn handover_offset(&self) -> Option<u32> {
+        self.handover_offset
+    }
+
+    /// The offset of the `kernel_info` structure from the protected-mode code
+    pub fn kernel_info_offset(&self) -> Option<u32> {
+        self.kernel_info_offset
+    }
+}
+
+impl fmt::Display for Header {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        super::fields::write_text(self, f)
+    }
+}
+
+/// The compressed kernel that a bzImage carries, and the ELF image inside it
+#[derive(Debug)]
+pub struct Payload {
+    offset: usize,
+    length: usize,
+    compression: Compression,
+    decompressed_size: u32,
+    kernel: Vec<u8>,
+    stopped: Option<String>,
+    elf: Result<Elf, Rejection>,
+    config: Result<KernelConfig, Rejection>,
+    mismatches: Vec<String>,
+}
+
+impl Payload {
+    /// Decompress and analyze the payload of the bzImage `image`, whose setup header is `header`
+    pub fn parse(header: &Header, image: &[u8]) -> Result<Payload, Rejection> {
+        Payload::parse_with_limit(header, image, MAX_FILE_SIZE)
+    }
+
+    /// Like `parse`, but stop decompressing the kernel once it reaches `limit` bytes
+    pub fn parse_with_limit(header: &Header,
+                            image: &[u8],
+                            limit: u64)
+                            -> Result<Payload, Rejection> {
+        let (payload_offset, length) = match (header.payload_offset, header.payload_length) {
+            (Some(offset), Some(length)) => (offset as usize, length as usize),
+            _ => {
+                return Err(Rejection::invalid(0x206,
+                                              format!("boot protocol {} does not describe the \
+                                                       payload",
+                                                      header.version())))
+            }
+        };
+        let offset = header.protected_mode_offset() + payload_offset;
+        let data = offset.checked_add(length)
+            .and_then(|end| image.get(offset..end))
+            .ok_or_else(|| {
+                            Rejection::invalid(PAYLOAD_OFFSET_OFFSET,
+                                               format!("payload of {} bytes at 0x{:x} runs past \
+                                                        the end of the data",
+                                                       length,
+                                                       offset))
+                        })?;
+
+        // The build appends the decompressed size of the kernel to the compressed data
+        if data.len() < 4 {
+            return Err(Rejection::invalid(offset, "payload is too short to hold its size"));
+        }
+        let (compressed, size) = data.split_at(data.len() - 4);
+        let decompressed_size = u32::from_le_bytes([size[0], size[1], size[2], size[3]]);
+
+        let compression = Compression::detect(compressed)
+            .ok_or_else(|| Rejection::invalid(offset, "unrecognized payload compression"))?;
+        let (kernel, stopped) = decompress(compressed, compression, limit);
+        let elf = Elf::parse(&kernel);
+        let config = KernelConfig::find(&kernel);
+        let mismatches = match config {
+            Ok(ref config) => config_mismatches(header, image, config),
+            Err(_) => vec![],
+        };
+
+        Ok(Payload {
+               offset,
+               length,
+               compression,
+               decompressed_size,
+               kernel,
+               stopped,
+               elf,
+               config,
+               mismatches,
+           })
+    }
+
+    /// The offset of the payload within the image
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The length of the payload, including the decompressed size at its end
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// The format the kernel is compressed with
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// The size of the kernel once decompressed, as recorded at the end of the payload
+    pub fn decompressed_size(&self) -> u32 {
+        self.decompressed_size
+    }
+
+    /// The decompressed kernel, which is cut short if decompression stopped early
+    pub fn kernel(&self) -> &[u8] {
+        &self.kernel
+    }
+
+    /// The kernel image, if it decompressed to an ELF image
+    pub fn elf(&self) -> Option<&Elf> {
+        self.elf.as_ref().ok()
+    }
+
+    /// The configuration embedded in the kernel, or why it could not be found
+    pub fn config(&self) -> Result<&KernelConfig, &Rejection> {
+        self.config.as_ref()
+    }
+}
+
+/// Where the header and the configuration the kernel was built with disagree
+fn config_mismatches(header: &Header, image: &[u8], config: &KernelConfig) -> Vec<String> {
+    let mut mismatches = vec![];
+    if let Some(relocatable) = header.relocatable_kernel {
+        if relocatable != config.enabled("RELOCATABLE") {
+            mismatches.push(format!("header says the kernel is {}relocatable, but \
+                                     CONFIG_RELOCATABLE is {}",
+                                    if relocatable { "" } else { "not " },
+                                    config.get("RELOCATABLE").unwrap_or("not set")));
+        }
+    }
+    // An EFI stub kernel doubles as a PE/COFF image, which starts with the MZ signature
+    let efi_stub = image.starts_with(b"MZ");
+    if efi_stub != config.enabled("EFI_STUB") {
+        mismatches.push(format!("image {} a PE/COFF header, but CONFIG_EFI_STUB is {}",
+                                if efi_stub { "has" } else { "does not have" },
+                                config.get("EFI_STUB").unwrap_or("not set")));
+    }
+    mismatches
+}
+
+/// Decompress as much of the data as possible, up to `limit` bytes, along with why decompression
+/// stopped early
+fn decompress(data: &[u8], compression: Compression, limit: u64) -> (Vec<u8>, Option<String>) {
+    let decoder = match compression.decoder(data) {
+        Ok(decoder) => decoder,
+        Err(e) => return (vec![], Some(e.to_string())),
+    };
+    // Whatever was decompressed before an error is still kept in `kernel`
+    let mut kernel = vec![];
+    match decoder.take(limit + 1).read_to_end(&mut kernel) {
+        Ok(_) if kernel.len() as u64 > limit => {
+            kernel.truncate(limit as usize);
+            (kernel, Some(format!("kernel is larger than the limit of {} bytes", limit)))
+        }
+        Ok(_) => (kernel, None),
+        Err(e) => (kernel, Some(e.to_string())),
+    }
+}
+
+impl super::BootInfo for Payload {
+    fn format(&self) -> &'static str {
+        "linux-payload"
+    }
+
+    fn title(&self) -> &'static str {
+        "Linux Kernel Payload"
+    }
+
+    fn fields(&self) -> Vec<Field> {
+        let mut fields = vec![Field::new("offset", "Offset", Value::offset(self.offset)),
+                              Field::new("compression",
+                                         "Compression",
+                                         Value::Text(self.compression.to_string())),
+                              Field::new("length",
+                                         "Compressed Size",
+                                         Value::Size(self.length as u64)),
+                              Field::new("decompressed_size",
+                                         "Kernel Size",
+                                         Value::Size(u64::from(self.decompressed_size)))];
+        if let Ok(ref config) = self.config {
+            fields.push(Field::new("config_offset",
+                                   "Config Offset",
+                                   Value::offset(config.offset())));
+        }
+        if let Ok(ref elf) = self.elf {
+            fields.extend(elf.fields());
+        }
+        fields
+    }
+
+    fn warnings(&self) -> Vec<String> {
+        let mut warnings = vec![];
+        if let Some(ref stopped) = self.stopped {
+            warnings.push(format!("decompression stopped after {} bytes: {}",
+                                  self.kernel.len(),
+                                  stopped));
+        } else if self.kernel.len() != self.decompressed_size as usize {
+            warnings.push(format!("payload decompressed to {} bytes, not the {} it records",
+                                  self.kernel.len(),
+                                  self.decompressed_size));
+        }
+        if let Err(ref rejection) = self.elf {
+            warnings.push(format!("decompressed kernel is not an ELF image: {}", rejection));
+        }
+        if let Err(Rejection::Invalid { ref reason, .. }) = self.config {
+            warnings.push(format!("embedded kernel config is unreadable: {}", reason));
+        }
+        warnings.extend(self.mismatches.iter().cloned());
+        warnings
+    }
+}
+
+impl fmt::Display for Payload {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        super::fields::write_text(self, f)
+    }
+}
+
+/// A chunk of the variable-length data at the end of `kernel_info`
+#[derive(Debug)]
+pub struct KernelInfoChunk {
+    offset: usize,
+    magic: [u8; 4],
+    size: u32,
+}
+
+impl KernelInfoChunk {
+    /// The offset of the chunk within the image
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The magic that identifies the chunk
+    pub fn magic(&self) -> [u8; 4] {
+        self.magic
+    }
+
+    /// The size of the chunk, including its magic and size
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    fn fields(&self) -> Vec<Field> {
+        vec![Field::new("offset", "Offset", Value::offset(self.offset)),
+             Field::new("size", "Size", Value::Size(u64::from(self.size)))]
+    }
+}
+
+/// The read-only `kernel_info` structure that boot protocol 2.15 adds to the protected-mode code
+#[derive(Debug)]
+pub struct KernelInfo {
+    offset: usize,
+    size: u32,
+    size_total: u32,
+    setup_type_max: u32,
+    chunks: Vec<KernelInfoChunk>,
+}
+
+impl KernelInfo {
+    /// Find and validate the `kernel_info` of the bzImage `image`, whose setup header is `header`
+    pub fn parse(header: &Header, image: &[u8]) -> Result<KernelInfo, Rejection> {
+        let kernel_info_offset = header.kernel_info_offset.ok_or(Rejection::NotPresent)?;
+        let offset = header.protected_mode_offset() + kernel_info_offset as usize;
+        let data = image.get(offset..)
+            .filter(|data| data.len() >= KERNEL_INFO_MIN_SIZE)
+            .ok_or_else(|| {
+                            Rejection::invalid(KERNEL_INFO_OFFSET_OFFSET,
+                                               format!("kernel_info at 0x{:x} runs past the end \
+                                                        of the data",
+                                                       offset))
+                        })?;
+        let u32_at = |at: usize| {
+            u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+        };
+
+        let magic = u32_at(0);
+        if magic != KERNEL_INFO_MAGIC {
+            return Err(Rejection::invalid(offset,
+                                          format!("kernel_info magic is 0x{:08x} instead of \
+                                                   \"LToP\"",
+                                                  magic)));
+        }
+        let size = u32_at(4);
+        let size_total = u32_at(8);
+        let setup_type_max = u32_at(12);
+
+        if (size as usize) < KERNEL_INFO_MIN_SIZE {
+            return Err(Rejection::invalid(offset + 4,
+                                          format!("kernel_info size of {} bytes is smaller than \
+                                                   its {} fixed bytes",
+                                                  size,
+                                                  KERNEL_INFO_MIN_SIZE)));
+        }
+        if size_total < size {
+            return Err(Rejection::invalid(offset + 8,
+                                          format!("kernel_info total size of {} bytes is smaller \
+                                                   than its size of {} bytes",
+                                                  size_total,
+                                                  size)));
+        }
+        if size_total as usize > data.len() {
+            return Err(Rejection::invalid(offset + 8,
+                                          format!("kernel_info of {} bytes runs past the end of \
+                                                   the data",
+                                                  size_total)));
+        }
+
+        // The variable-length data is a sequence of chunks that each start with a magic and size
+        let mut chunks = vec![];
+        let mut chunk_offset = size as usize;
+        while chunk_offset < size_total as usize {
+            if size_total as usize - chunk_offset < 8 {
+                return Err(Rejection::invalid(offset + chunk_offset,
+                                              "kernel_info chunk header is truncated"));
+            }
+            let mut magic = [0u8; 4];
+            magic.copy_from_slice(&data[chunk_offset..chunk_offset + 4]);
+            let chunk_size = u32_at(chunk_offset + 4);
+            if chunk_size < 8 || chunk_size as usize > size_total as usize - chunk_offset {
+                return Err(Rejection::invalid(offset + chunk_offset + 4,
+                                              format!("kernel_info chunk of {} bytes does not \
+                                                       fit in the variable-length data",
+                                                      chunk_size)));
+            }
+            chunks.push(KernelInfoChunk {
+                            offset: offset + chunk_offset,
+                            magic,
+                            size: chunk_size,
+                        });
+            chunk_offset += chunk_size as usize;
+        }
+
+        Ok(KernelInfo {
+               offset,
+               size,
+               size_total,
+               setup_type_max,
+               chunks,
+           })
+    }
+
+    /// The offset of the structure within the image
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The size of the fixed part of the structure
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// The size of the structure including its variable-length data
+    pub fn size_total(&self) -> u32 {
+        self.size_total
+    }
+
+    /// The largest `setup_data` type that the kernel understands
+    pub fn setup_type_max(&self) -> u32 {
+        self.setup_type_max
+    }
+
+    /// The chunks of variable-length data
+    pub fn chunks(&self) -> &[KernelInfoChunk] {
+        &self.chunks
+    }
+}
+
+/// The name of a `setup_data` type
+fn setup_data_name(typ: u32) -> &'static str {
+    match typ {
+        0 => "none",
+        1 => "E820 extended",
+        2 => "DTB",
+        3 => "PCI",
+        4 => "EFI",
+        5 => "applesmc",
+        6 => "jailhouse",
+        7 => "CC blob",
+        8 => "IMA",
+        9 => "RNG seed",
+        _ => "unknown",
+    }
+}
+
+impl super::BootInfo for KernelInfo {
+    fn format(&self) -> &'static str {
+        "linux-kernel-info"
+    }
+
+    fn title(&self) -> &'static str {
+        "Linux Kernel Info"
+    }
+
+    fn fields(&self) -> Vec<Field> {
+        vec![Field::new("offset", "Offset", Value::offset(self.offset)),
+             Field::new("size", "Size", Value::Size(u64::from(self.size))),
+             Field::new("size_total", "Total Size", Value::Size(u64::from(self.size_total))),
+             Field::new("setup_type_max",
+                        "Setup Type Max",
+                        Value::Enum {
+                            value: u64::from(self.setup_type_max & !SETUP_INDIRECT),
+                            name: setup_data_name(self.setup_type_max & !SETUP_INDIRECT),
+                        }),
+             Field::new("setup_indirect",
+                        "Indirect Setup Data",
+                        Value::Bool(self.setup_type_max & SETUP_INDIRECT != 0)),
+             Field::new("chunks",
+                        "Variable Data",
+                        Value::Tags(self.chunks
+                                        .iter()
+                                        .map(|chunk| {
+                                                 let magic = String::from_utf8_lossy(&chunk.magic);
+                                                 Group {
+                                                     title: format!("Chunk: {}", magic),
+                                                     fields: chunk.fields(),
+                                                 }
+                                             })
+                                        .collect()))]
+    }
+}
+
+impl fmt::Display for KernelInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        super::fields::write_text(self, f)
+    }
+}
+
+/// An entry point that the `.compat` section offers to firmware of another architecture, such as
+/// 32-bit firmware booting a 64-bit kernel in mixed mode
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompatEntry {
+    offset: usize,
+    machine: u16,
+    entry: u32,
+}
+
+impl CompatEntry {
+    /// The offset of the entry within the image
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The machine type of the firmware that uses the entry point
+    pub fn machine(&self) -> u16 {
+        self.machine
+    }
+
+    /// The entry point, relative to the image base
+    pub fn entry(&self) -> u32 {
+        self.entry
+    }
+
+    fn fields(&self) -> Vec<Field> {
+        vec![Field::new("offset", "Offset", Value::offset(self.offset)),
+             Field::new("machine",
+                        "Machine",
+                        Value::Enum {
+                            value: u64::from(self.machine),
+                            name: pe::machine_name(self.machine),
+                        }),
+             Field::new("entry", "Entry", Value::hex32(self.entry))]
+    }
+}
+
+/// Decode the entries of the `.compat` section, along with why decoding stopped early
+fn compat_entries(data: &[u8], offset: usize) -> (Vec<CompatEntry>, Option<String>) {
+    let mut entries = vec![];
+    let mut pos = 0;
+    // The entries end at a zero type, or at the end of the section
+    while pos < data.len() && data[pos] != 0 {
+        let typ = data[pos];
+        let size = match data.get(pos + 1) {
+            Some(&size) if size >= 2 && pos + usize::from(size) <= data.len() => usize::from(size),
+            _ => return (entries, Some(format!("truncated .compat entry at 0x{:x}", offset + pos))),
+        };
+        if typ == COMPAT_PE_ENTRY && size >= 8 {
+            let entry = &data[pos..pos + 8];
+            entries.push(CompatEntry {
+                             offset: offset + pos,
+                             machine: u16::from_le_bytes([entry[2], entry[3]]),
+                             entry: u32::from_le_bytes([entry[4], entry[5], entry[6], entry[7]]),
+                         });
+        }
+        pos += size;
+    }
+    (entries, None)
+}
+
+/// The PE/COFF image that a bzImage built with `CONFIG_EFI_STUB` doubles as
+#[derive(Debug)]
+pub struct EfiStub {
+    pe: Pe,
+    protected_mode_offset: usize,
+    entry_address: Option<u32>,
+    handover: Vec<(&'static str, &'static str, usize)>,
+    compat: Vec<CompatEntry>,
+    mismatches: Vec<String>,
+}
+
+impl EfiStub {
+    /// Parse the PE headers of the bzImage `image`, whose setup header is `header`, and check
+    /// them against the setup header
+    pub fn parse(header: &Header, image: &[u8]) -> Result<EfiStub, Rejection> {
+        let pe = Pe::parse(image)?;
+        let protected_mode_offset = header.protected_mode_offset();
+        let protected_mode_end = protected_mode_offset + header.syssize as usize * 16;
+        let mut mismatches = vec![];
+
+        let text = pe.section(".text");
+        let in_text = |offset: usize| {
+            text.is_some_and(|text| {
+                                 offset >= text.raw_offset() as usize &&
+                                 offset - (text.raw_offset() as usize) <
+                                 text.raw_size() as usize
+                             })
+        };
+        match text {
+            Some(text) if text.raw_offset() as usize != protected_mode_offset => {
+                mismatches.push(format!(".text starts at 0x{:x} instead of at the protected-mode \
+                                         code at 0x{:x}",
+                                        text.raw_offset(),
+                                        protected_mode_offset))
+            }
+            Some(_) => {}
+            None => mismatches.push("there is no .text section".into()),
+        }
+
+        // Once loaded through the boot protocol, the protected-mode code is at code32_start
+        let entry = pe.rva_to_offset(pe.entry());
+        let entry_address = match entry {
+            Some(entry) if entry >= protected_mode_offset && entry < protected_mode_end => {
+                header.code32_start
+                    .map(|start| start.wrapping_add((entry - protected_mode_offset) as u32))
+            }
+            _ => {
+                mismatches.push(format!("PE entry point 0x{:x} is outside of the protected-mode \
+                                         code",
+                                        pe.entry()));
+                None
+            }
+        };
+        if entry.is_some_and(|entry| !in_text(entry)) {
+            mismatches.push(format!("PE entry point 0x{:x} is outside of .text", pe.entry()));
+        }
+
+        let mut handover = vec![];
+        if let (Some(offset), Some(xloadflags)) = (header.handover_offset, header.xloadflags) {
+            let entry = protected_mode_offset + offset as usize;
+            if xloadflags.contains(XLoadFlags::EFI_HANDOVER_32) {
+                handover.push(("handover_32", "Handover Entry (32-bit)", entry));
+            }
+            if xloadflags.contains(XLoadFlags::EFI_HANDOVER_64) {
+                handover.push(("handover_64",
+                               "Handover Entry (64-bit)",
+                               entry + HANDOVER_64_OFFSET));
+            }
+        }
+        for &(_, label, entry) in &handover {
+            if !in_text(entry) {
+                mismatches.push(format!("{} at 0x{:x} is outside of .text", label, entry));
+            }
+        }
+
+        let compat = match pe.section(".compat") {
+            Some(section) => {
+                match section.data(image) {
+                    Some(data) => {
+                        let (entries, stopped) = compat_entries(data,
+                                                                section.raw_offset() as usize);
+                        mismatches.extend(stopped);
+                        entries
+                    }
+                    None => {
+                        mismatches.push(".compat section lies past the end of the data".into());
+                        vec![]
+                    }
+                }
+            }
+            None => vec![],
+        };
+        for entry in &compat {
+            if !pe.rva_to_offset(entry.entry).is_some_and(&in_text) {
+                mismatches.push(format!("{} entry point 0x{:x} is outside of .text",
+                                        pe::machine_name(entry.machine),
+                                        entry.entry));
+            }
+        }
+
+        Ok(EfiStub {
+               pe,
+               protected_mode_offset,
+               entry_address,
+               handover,
+               compat,
+               mismatches,
+           })
+    }
+
+    /// The PE/COFF headers
+    pub fn pe(&self) -> &Pe {
+        &self.pe
+    }
+
+    /// The address of the PE entry point once the protected-mode code is loaded at
+    /// `code32_start`
+    pub fn entry_address(&self) -> Option<u32> {
+        self.entry_address
+    }
+
+    /// The entry points in the `.compat` section
+    pub fn compat(&self) -> &[CompatEntry] {
+        &self.compat
+    }
+}
+
+impl super::BootInfo for EfiStub {
+    fn format(&self) -> &'static str {
+        "linux-efi"
+    }
+
+    fn title(&self) -> &'static str {
+        "Linux EFI Stub"
+    }
+
+    fn fields(&self) -> Vec<Field> {
+        let mut fields = vec![Field::new("offset", "Offset", Value::offset(0))];
+        fields.extend(self.pe.header_fields());
+        fields.push(Field::new("protected_mode_offset",
+                               "PM Code Offset",
+                               Value::offset(self.protected_mode_offset)));
+        if let Some(entry_address) = self.entry_address {
+            fields.push(Field::new("entry_address", "Loaded Entry", Value::hex32(entry_address)));
+        }
+        for &(name, label, entry) in &self.handover {
+            fields.push(Field::new(name, label, Value::offset(entry)));
+        }
+        fields.push(Field::new("compat",
+                               "Compat Entries",
+                               Value::Tags(self.compat
+                                               .iter()
+                                               .map(|entry| {
+                                                        let machine =
+                                                            pe::machine_name(entry.machine);
+                                                        Group {
+                                                            title: format!("Compat: {}", machine),
+                                                            fields: entry.fields(),
+                                                        }
+                                                    })
+                                               .collect())));
+        fields.push(Field::new("sections", "Sections", self.pe.section_groups()));
+        fields
+    }
+
+    fn warnings(&self) -> Vec<String> {
+        self.mismatches.clone()
+    }
+}
+
+impl fmt::Display for EfiStub {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        super::fields::write_text(self, f)
+    }
+}
+
+/// Parse the setup header and the EFI stub of a bzImage
+fn parse_efi_stub(buf: bytes::Bytes) -> Result<EfiStub, Rejection> {
+    let header = Header::parse(buf.clone())?;
+    EfiStub::parse(&header, &buf)
+}
+
+pub fn register(descs: &mut Vec<super::Descriptor>) {
+    descs.push(super::Descriptor {
+                   name: "linux",
+                   max_range: 32768,
+                   parser: Box::new(|buf| {
+                       Header::parse(buf).map(|h| Box::new(h) as Box<dyn super::BootInfo>)
+                   }),
+                   // There is only ever one place for the setup header
+                   scanner: Box::new(|buf| {
+                       Header::parse(buf).map(|h| vec![Box::new(h) as Box<dyn super::BootInfo>])
+                   }),
+               });
+    descs.push(super::Descriptor {
+                   name: "linux-efi",
+                   max_range: 32768,
+                   parser: Box::new(|buf| {
+                       parse_efi_stub(buf).map(|s| Box::new(s) as Box<dyn super::BootInfo>)
+                   }),
+                   scanner: Box::new(|buf| {
+                       parse_efi_stub(buf).map(|s| vec![Box::new(s) as Box<dyn super::BootInfo>])
+                   }),
+               })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parsers::builders::elf64;
+    use parsers::BootInfo;
+    use std::io;
+    use utils;
+    const MULTIBOOT1: &[u8; 40000] = include_bytes!("../../test-data/multiboot1");
+    const MULTIBOOT2: &[u8; 40000] = include_bytes!("../../test-data/multiboot2");
+    const LINUXBOOT: &[u8; 40000] = include_bytes!("../../test-data/linuxboot");
+
+    #[test]
+    #[should_panic]
+    fn parse_invalid_multiboot1() {
+        let cursor = io::Cursor::new(MULTIBOOT1.as_ref());
+        let bytes = utils::header_bytes(cursor, 8192).unwrap();
+        Header::parse(bytes).unwrap();
+    }
+
+    #[test]
+    #[should_panic]
+    fn parse_invalid_multiboot2() {
+        let cursor = io::Cursor::new(MULTIBOOT2.as_ref());
+        let bytes = utils::header_bytes(cursor, 32768).unwrap();
+        Header::parse(bytes).unwrap();
+    }
+
+    fn linuxboot_with(offset: usize, value: u8) -> bytes::Bytes {
+        let mut data = LINUXBOOT[..32768].to_vec();
+        data[offset] = value;
+        data.into()
+    }
+
+    #[test]
+    fn parse_zero_setup_sects() {
+        let header = Header::parse(linuxboot_with(0x1f1, 0)).unwrap();
+        assert_eq!(header.setup_sects(), 0);
+    }
+
+    #[test]
+    fn parse_max_setup_sects() {
+        let mut data = LINUXBOOT.to_vec();
+        data[0x1f1] = 0xff;
+        data.resize(0x200 * 0x100, 0);
+        let header = Header::parse(data.into()).unwrap();
+        assert_eq!(header.setup_sects(), 0xff);
+    }
+
+    #[test]
+    fn reject_oversized_min_alignment() {
+        match Header::parse(linuxboot_with(0x235, 40)) {
+            Err(Rejection::Invalid { offset, .. }) => assert_eq!(offset, 0x235),
+            other => panic!("unexpected outcome {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reject_truncated_header() {
+        let mut data = LINUXBOOT[..0x210].to_vec();
+        data[0x1f1] = 1;
+        match Header::parse(data.into()) {
+            Err(Rejection::Invalid { .. }) => {}
+            other => panic!("unexpected outcome {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reject_bad_boot_flag() {
+        match Header::parse(linuxboot_with(0x1fe, 0)) {
+            Err(Rejection::Invalid { offset, .. }) => assert_eq!(offset, 0x1fe),
+            other => panic!("unexpected outcome {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reject_bad_jump() {
+        match Header::parse(linuxboot_with(0x200, 0xe9)) {
+            Err(Rejection::Invalid { offset, .. }) => assert_eq!(offset, 0x200),
+            other => panic!("unexpected outcome {:?}", other),
+        }
+        match Header::parse(linuxboot_with(0x201, 0)) {
+            Err(Rejection::Invalid { offset, .. }) => assert_eq!(offset, 0x200),
+            other => panic!("unexpected outcome {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_jump_with_one_setup_sector() {
+        // The furthest a short jump reaches is still within a single setup sector
+        let mut data = LINUXBOOT[..32768].to_vec();
+        data[0x1f1] = 1;
+        data[0x201] = 0x7f;
+        let header = Header::parse(data.into()).unwrap();
+        assert_eq!(header.setup_sects(), 1);
+        assert_eq!(header.jump_target(), Some(0x281));
+        assert_eq!(header.protected_mode_offset(), 0x400);
+    }
+
+    #[test]
+    fn decode_setup_header_fields() {
+        let mut data = LINUXBOOT[..32768].to_vec();
+        data[0x1fa..0x1fc].copy_from_slice(&0x0f01u16.to_le_bytes());
+        data[0x1fc..0x1fe].copy_from_slice(&0x0803u16.to_le_bytes());
+        data[0x210] = 0x72;
+        let header = Header::parse(data.into()).unwrap();
+        assert_eq!(header.jump_target(), Some(0x268));
+        let fields = header.fields();
+        let value = |name| {
+            fields.iter()
+                .find(|f| f.name == name)
+                .unwrap()
+                .value
+                .to_string()
+        };
+        assert_eq!(value("vid_mode"), "80x50 (3841)");
+        assert_eq!(value("root_dev"), "8:3");
+        assert_eq!(value("type_of_loader"), "GRUB (114)");
+        assert_eq!(value("hardware_subarch"), "PC (0)");
+        assert_eq!(value("jump"), "0x00000268");
+    }
+
+    /// A zImage boot sector from before boot protocol 2.0, followed by four setup sectors
+    fn legacy_boot_sector(code: &[u8]) -> Vec<u8> {
+        let mut data = vec![0u8; 0x200 * 5];
+        data[..code.len()].copy_from_slice(code);
+        data[0x1f1] = 4;
+        data[0x1f4..0x1f8].copy_from_slice(&0xffff_1000u32.to_le_bytes());
+        data[0x1fa..0x1fc].copy_from_slice(&0xfffdu16.to_le_bytes());
+        data[0x1fc..0x1fe].copy_from_slice(&0x0301u16.to_le_bytes());
+        data[0x1fe..0x200].copy_from_slice(&BOOT_FLAG.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn parse_legacy_boot_sector() {
+        for code in &[&[0xb8, 0xc0, 0x07][..], &[0xea, 0x05, 0x00, 0xc0, 0x07][..]] {
+            let header = Header::parse(legacy_boot_sector(code).into()).unwrap();
+            assert!(header.legacy());
+            assert_eq!(header.header(), None);
+            assert_eq!(header.jump_target(), None);
+            assert_eq!(header.syssize(), 0x1000);
+            assert_eq!(header.protected_mode_offset(), 0x200 * 5);
+            let fields = header.fields();
+            let names: Vec<_> = fields.iter().map(|f| f.name).collect();
+            assert_eq!(names,
+                       ["offset", "version", "setup_sects", "root_flags", "syssize", "ram_size",
+                        "vid_mode", "root_dev", "boot_flag"]);
+            assert_eq!(fields[1].value.to_string(), "pre-2.0");
+            assert_eq!(fields[6].value.to_string(), "ask (65533)");
+            assert_eq!(fields[7].value.to_string(), "3:1");
+        }
+    }
+
+    #[test]
+    fn ignore_other_boot_sectors() {
+        let data = legacy_boot_sector(&[0xfa, 0x33, 0xc0]);
+        assert_eq!(Header::parse(data.into()).unwrap_err(), Rejection::NotPresent);
+    }
+
+    #[test]
+    fn reject_oversized_zimage() {
+        let mut data = legacy_boot_sector(&[0xb8, 0xc0, 0x07]);
+        data[0x1f4..0x1f6].copy_from_slice(&0x9000u16.to_le_bytes());
+        match Header::parse(data.into()) {
+            Err(Rejection::Invalid { offset, .. }) => assert_eq!(offset, 0x1f4),
+            other => panic!("unexpected outcome {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_protocol_1_x() {
+        let mut data = LINUXBOOT[..32768].to_vec();
+        data[0x206..0x208].copy_from_slice(&0x0105u16.to_le_bytes());
+        let header = Header::parse(data.into()).unwrap();
+        assert!(!header.legacy());
+        assert_eq!(header.version(), "1.5");
+        assert_eq!(header.syssize(), 0x3831);
+        assert_eq!(header.kernel_version(), None);
+        assert_eq!(header.load_flags(), None);
+        assert_eq!(header.code32_start(), None);
+    }
+
+    #[test]
+    fn parse_payload_fields() {
+        let header = Header::parse(LINUXBOOT[..32768].to_vec().into()).unwrap();
+        assert_eq!(header.payload_offset(), Some(0x3b4));
+        assert_eq!(header.payload_length(), Some(0x7063d2));
+        assert_eq!(header.pref_address(), Some(0x100_0000));
+    }
+
+    #[test]
+    fn decode_load_flags() {
+        let header = Header::parse(LINUXBOOT[..32768].to_vec().into()).unwrap();
+        assert_eq!(header.load_flags(), Some(LoadFlags::LOADED_HIGH));
+        assert_eq!(header.xloadflags().unwrap().names(),
+                   ["kernel-64", "can-be-loaded-above-4g", "efi-handover-32", "efi-handover-64",
+                    "efi-kexec"]);
+        assert!(header.warnings().is_empty());
+    }
+
+    #[test]
+    fn flag_unknown_load_flags() {
+        let header = Header::parse(linuxboot_with(0x211, 0x85)).unwrap();
+        assert_eq!(header.load_flags().unwrap().names(),
+                   ["loaded-high", "can-use-heap", "unknown"]);
+        assert_eq!(header.warnings(), ["unknown loadflags bits 0x04"]);
+        let fields = header.fields();
+        let load_flags = fields.iter().find(|f| f.name == "load_flags").unwrap();
+        assert_eq!(load_flags.value.to_json(),
+                   json!({ "bits": 0x85, "names": ["loaded-high", "can-use-heap", "unknown"] }));
+    }
+
+    /// The test bzImage, with its payload replaced by `payload`
+    fn bzimage_with_payload(payload: &[u8]) -> Vec<u8> {
+        let mut data = LINUXBOOT[..0x47b4].to_vec();
+        data[0x24c..0x250].copy_from_slice(&(payload.len() as u32).to_le_bytes());
+        data.extend(payload);
+        data
+    }
+
+    #[test]
+    fn analyze_gzip_payload() {
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        let kernel = elf64(0x0100_0000, &[1, 2, 3, 4]);
+        let mut encoder = GzEncoder::new(vec![], ::flate2::Compression::default());
+        encoder.write_all(&kernel).unwrap();
+        let mut payload = encoder.finish().unwrap();
+        payload.extend(&(kernel.len() as u32).to_le_bytes());
+
+        let image = bzimage_with_payload(&payload);
+        let header = Header::parse(image.clone().into()).unwrap();
+        let payload = Payload::parse(&header, &image).unwrap();
+        assert_eq!(payload.offset(), 0x47b4);
+        assert_eq!(payload.compression(), Compression::Gzip);
+        assert_eq!(payload.decompressed_size() as usize, kernel.len());
+        let elf = payload.elf().unwrap();
+        assert_eq!(elf.entry(), 0x0100_0000);
+        assert_eq!(elf.build_id(), Some(&[1, 2, 3, 4][..]));
+        assert_eq!(payload.kernel(), &kernel[..]);
+        assert!(payload.warnings().is_empty());
+    }
+
+    #[test]
+    fn stop_payload_at_limit() {
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        let kernel = vec![0x90u8; 0x10000];
+        let mut encoder = GzEncoder::new(vec![], ::flate2::Compression::default());
+        encoder.write_all(&kernel).unwrap();
+        let mut payload = encoder.finish().unwrap();
+        payload.extend(&(kernel.len() as u32).to_le_bytes());
+
+        let image = bzimage_with_payload(&payload);
+        let header = Header::parse(image.clone().into()).unwrap();
+        let payload = Payload::parse_with_limit(&header, &image, 0x1000).unwrap();
+        assert_eq!(payload.kernel(), &kernel[..0x1000]);
+        assert_eq!(payload.warnings()[0],
+                   "decompression stopped after 4096 bytes: kernel is larger than the limit of \
+                    4096 bytes");
+    }
+
+    #[test]
+    fn cross_check_embedded_config() {
+        use flate2::write::GzEncoder;
+        use parsers::builders::{embedded, CONFIG};
+        use std::io::Write;
+
+        let mut kernel = elf64(0x0100_0000, &[1, 2, 3, 4]);
+        kernel.extend(embedded(CONFIG));
+        let mut encoder = GzEncoder::new(vec![], ::flate2::Compression::default());
+        encoder.write_all(&kernel).unwrap();
+        let mut payload = encoder.finish().unwrap();
+        payload.extend(&(kernel.len() as u32).to_le_bytes());
+
+        let image = bzimage_with_payload(&payload);
+        let header = Header::parse(image.clone().into()).unwrap();
+        let payload = Payload::parse(&header, &image).unwrap();
+        assert_eq!(payload.config().unwrap().get("LOCALVERSION"), Some("\"-test\""));
+        // The test image is relocatable, but the embedded config says otherwise
+        let warnings = payload.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("CONFIG_RELOCATABLE"));
+    }
+
+    /// The test bzImage upgraded to boot protocol 2.15, with `kernel_info` 0x100 bytes into the
+    /// protected-mode code
+    fn bzimage_with_kernel_info(kernel_info: &[u8]) -> Vec<u8> {
+        let mut data = LINUXBOOT[..32768].to_vec();
+        data[0x206..0x208].copy_from_slice(&0x020fu16.to_le_bytes());
+        data[0x268..0x26c].copy_from_slice(&0x100u32.to_le_bytes());
+        data[0x4500..0x4500 + kernel_info.len()].copy_from_slice(kernel_info);
+        data
+    }
+
+    fn kernel_info(size: u32, size_total: u32, setup_type_max: u32, data: &[u8]) -> Vec<u8> {
+        let mut kernel_info = b"LToP".to_vec();
+        kernel_info.extend(&size.to_le_bytes());
+        kernel_info.extend(&size_total.to_le_bytes());
+        kernel_info.extend(&setup_type_max.to_le_bytes());
+        kernel_info.extend(data);
+        kernel_info
+    }
+
+    #[test]
+    fn parse_kernel_info() {
+        let image = bzimage_with_kernel_info(&kernel_info(0x10,
+                                                          0x1c,
+                                                          0x8000_0009,
+                                                          b"0123\x0c\0\0\0Data"));
+        let header = Header::parse(image.clone().into()).unwrap();
+        assert_eq!(header.kernel_info_offset(), Some(0x100));
+        let kernel_info = KernelInfo::parse(&header, &image).unwrap();
+        assert_eq!(kernel_info.offset(), 0x4500);
+        assert_eq!(kernel_info.size(), 0x10);
+        assert_eq!(kernel_info.size_total(), 0x1c);
+        assert_eq!(kernel_info.chunks().len(), 1);
+        assert_eq!(&kernel_info.chunks()[0].magic(), b"0123");
+        assert_eq!(kernel_info.chunks()[0].offset(), 0x4510);
+        let fields = kernel_info.fields();
+        let setup_type_max = fields.iter().find(|f| f.name == "setup_type_max").unwrap();
+        assert_eq!(setup_type_max.value.to_string(), "RNG seed (9)");
+    }
+
+    #[test]
+    fn kernel_info_needs_2_15() {
+        let header = Header::parse(LINUXBOOT[..32768].to_vec().into()).unwrap();
+        assert_eq!(header.kernel_info_offset(), None);
+        assert_eq!(KernelInfo::parse(&header, LINUXBOOT).unwrap_err(), Rejection::NotPresent);
+    }
+
+    #[test]
+    fn reject_bad_kernel_info() {
+        let cases: Vec<(Vec<u8>, usize)> =
+            vec![(b"PoTL\x10\0\0\0\x10\0\0\0\0\0\0\0".to_vec(), 0x4500),
+                 (kernel_info(0x0c, 0x10, 0, &[]), 0x4504),
+                 (kernel_info(0x10, 0x0c, 0, &[]), 0x4508),
+                 (kernel_info(0x10, 0x10000, 0, &[]), 0x4508),
+                 (kernel_info(0x10, 0x18, 0, b"0123\x10\0\0\0"), 0x4514),
+                 (kernel_info(0x10, 0x14, 0, b"0123"), 0x4510)];
+        for (kernel_info, expected) in cases {
+            let image = bzimage_with_kernel_info(&kernel_info);
+            let header = Header::parse(image.clone().into()).unwrap();
+            match KernelInfo::parse(&header, &image) {
+                Err(Rejection::Invalid { offset, .. }) => assert_eq!(offset, expected),
+                other => panic!("unexpected outcome {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn parse_efi_stub() {
+        let image = LINUXBOOT[..32768].to_vec();
+        let header = Header::parse(image.clone().into()).unwrap();
+        let stub = EfiStub::parse(&header, &image).unwrap();
+        assert_eq!(stub.pe().machine(), pe::MACHINE_AMD64);
+        assert_eq!(stub.pe().entry(), 0x4610);
+        let names: Vec<_> = stub.pe().sections().iter().map(|s| s.name()).collect();
+        assert_eq!(names, [".setup", ".reloc", ".text", ".bss"]);
+        assert_eq!(stub.entry_address(), Some(0x0010_0210));
+        assert!(stub.compat().is_empty());
+        assert!(stub.warnings().is_empty());
+        let fields = stub.fields();
+        let handover: Vec<_> = fields.iter()
+            .filter(|f| f.name.starts_with("handover"))
+            .map(|f| f.value.to_string())
+            .collect();
+        assert_eq!(handover, ["0x00004590", "0x00004790"]);
+    }
+
+    #[test]
+    fn decode_compat_entries() {
+        // Turn .reloc into a .compat section with an ia32 entry point and an unknown entry
+        let mut image = LINUXBOOT[..32768].to_vec();
+        image[0x162..0x16a].copy_from_slice(b".compat\0");
+        let compat = [2, 4, 0xff, 0xff, 1, 8, 0x4c, 0x01, 0x10, 0x46, 0, 0, 0];
+        image[0x43e0..0x43e0 + compat.len()].copy_from_slice(&compat);
+        let header = Header::parse(image.clone().into()).unwrap();
+        let stub = EfiStub::parse(&header, &image).unwrap();
+        assert_eq!(stub.compat(),
+                   [CompatEntry {
+                        offset: 0x43e4,
+                        machine: pe::MACHINE_I386,
+                        entry: 0x4610,
+                    }]);
+        assert!(stub.warnings().is_empty());
+
+        let (entries, stopped) = compat_entries(&[1, 8, 0x4c, 0x01], 0x100);
+        assert!(entries.is_empty());
+        assert_eq!(stopped, Some("truncated .compat entry at 0x100".to_string()));
+    }
+
+    #[test]
+    fn flag_efi_entry_outside_of_text() {
+        // Point the PE entry point into the setup code
+        let mut image = LINUXBOOT[..32768].to_vec();
+        image[0xaa..0xae].copy_from_slice(&0x300u32.to_le_bytes());
+        let header = Header::parse(image.clone().into()).unwrap();
+        let stub = EfiStub::parse(&header, &image).unwrap();
+        assert_eq!(stub.entry_address(), None);
+        assert_eq!(stub.warnings(),
+                   ["PE entry point 0x300 is outside of the protected-mode code",
+                    "PE entry point 0x300 is outside of .text"]);
+    }
+
+    #[test]
+    fn efi_stub_needs_mz() {
+        let mut image = LINUXBOOT[..32768].to_vec();
+        image[0] = 0;
+        let header = Header::parse(image.clone().into()).unwrap();
+        assert_eq!(EfiStub::parse(&header, &image).unwrap_err(), Rejection::NotPresent);
+    }
+
+    #[test]
+    fn split_setup_from_kernel() {
+        let header = Header::parse(LINUXBOOT[..32768].to_vec().into()).unwrap();
+        let (setup, kernel) = header.split(LINUXBOOT).unwrap();
+        assert_eq!(setup.len(), (0x21 + 1) * 512);
+        assert_eq!(kernel.len(), LINUXBOOT.len() - setup.len());
+        assert!(header.split(&LINUXBOOT[..setup.len() - 1]).is_none());
+    }
+
+    #[test]
+    fn reject_payload_past_end() {
+        let header = Header::parse(LINUXBOOT[..32768].to_vec().into()).unwrap();
+        match Payload::parse(&header, LINUXBOOT) {
+            Err(Rejection::Invalid { offset, .. }) => assert_eq!(offset, PAYLOAD_OFFSET_OFFSET),
+            other => panic!("unexpected outcome {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reject_unknown_payload_compression() {
+        let image = bzimage_with_payload(&[0u8; 64]);
+        let header = Header::parse(image.clone().into()).unwrap();
+        match Payload::parse(&header, &image) {
+            Err(Rejection::Invalid { offset, .. }) => assert_eq!(offset, 0x47b4),
+            other => panic!("unexpected outcome {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_valid_linuxboot() {
+        let cursor = io::Cursor::new(LINUXBOOT.as_ref());
+        let bytes = utils::header_bytes(cursor, 32768).unwrap();
+        Header::parse(bytes).unwrap();
+    }
+
+    #[test]
+    fn parse_header_before_end_of_setup_code() {
+        // Only the setup header needs to be within the data, not all 33 setup sectors
+        let header = Header::parse(bytes::Bytes::from(&LINUXBOOT[..8192])).unwrap();
+        assert_eq!(header.setup_sects(), 33);
     }
 }
@@ -1,29 +1,162 @@
-use bytes;
-use std::fmt::Display;
+use bytes::{self, Buf};
+use std::fmt::{self, Display};
+use std::io::Cursor;
 
-pub trait BootInfo: Display {}
-pub type ParseBootInfo = fn(bytes::Bytes) -> Option<Box<BootInfo>>;
+pub use self::fields::{Field, Group, Value};
+
+/// A parsed header that can describe itself as a tree of fields
+///
+/// The `Display` implementation of every header renders these fields as text.
+pub trait BootInfo: Display {
+    /// The name of the format, matching the name of the `Descriptor` that parsed it
+    fn format(&self) -> &'static str;
+
+    /// A human-readable title for the header
+    fn title(&self) -> &'static str;
+
+    /// The fields of the header, in display order
+    fn fields(&self) -> Vec<Field>;
+
+    /// Problems with the header that did not stop it from being parsed
+    fn warnings(&self) -> Vec<String> {
+        vec![]
+    }
+}
+/// Why a parser did not produce a header
+#[derive(Clone, Debug, PartialEq)]
+pub enum Rejection {
+    /// There was no sign of the format in the data
+    NotPresent,
+    /// The format was recognized at `offset`, but the header there is not valid
+    Invalid { offset: usize, reason: String },
+}
+
+impl Rejection {
+    pub fn invalid<S: Into<String>>(offset: usize, reason: S) -> Rejection {
+        Rejection::Invalid {
+            offset,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            Rejection::NotPresent => write!(f, "not present"),
+            Rejection::Invalid { offset, ref reason } => {
+                write!(f, "invalid header at offset 0x{:08x}: {}", offset, reason)
+            }
+        }
+    }
+}
+
+/// Bounds-checked reads, so that truncated or malicious input is rejected instead of panicking
+pub(crate) trait TryBuf {
+    /// Fail with a `Rejection` at the current position unless `len` more bytes are available
+    fn need(&self, len: usize) -> Result<(), Rejection>;
+    fn try_advance(&mut self, len: usize) -> Result<(), Rejection>;
+    fn try_get_u8(&mut self) -> Result<u8, Rejection>;
+    fn try_get_u16_le(&mut self) -> Result<u16, Rejection>;
+    fn try_get_u32_le(&mut self) -> Result<u32, Rejection>;
+    fn try_get_u64_le(&mut self) -> Result<u64, Rejection>;
+}
+
+impl TryBuf for Cursor<bytes::Bytes> {
+    fn need(&self, len: usize) -> Result<(), Rejection> {
+        if self.remaining() < len {
+            Err(Rejection::invalid(self.position() as usize,
+                                   format!("data is truncated, needed {} bytes but only {} remain",
+                                           len,
+                                           self.remaining())))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn try_advance(&mut self, len: usize) -> Result<(), Rejection> {
+        self.need(len)?;
+        self.advance(len);
+        Ok(())
+    }
+
+    fn try_get_u8(&mut self) -> Result<u8, Rejection> {
+        self.need(1)?;
+        Ok(self.get_u8())
+    }
+
+    fn try_get_u16_le(&mut self) -> Result<u16, Rejection> {
+        self.need(2)?;
+        Ok(self.get_u16_le())
+    }
+
+    fn try_get_u32_le(&mut self) -> Result<u32, Rejection> {
+        self.need(4)?;
+        Ok(self.get_u32_le())
+    }
+
+    fn try_get_u64_le(&mut self) -> Result<u64, Rejection> {
+        self.need(8)?;
+        Ok(self.get_u64_le())
+    }
+}
+
+/// Every offset in `buf` at which the little-endian `magic` value appears, aligned or not
+pub(crate) fn find_magic(buf: &[u8], magic: u32) -> impl Iterator<Item = usize> + '_ {
+    let magic = magic.to_le_bytes();
+    buf.windows(magic.len())
+        .enumerate()
+        .filter(move |&(_, window)| window == magic)
+        .map(|(offset, _)| offset)
+}
+
+pub type ParseBootInfo = Box<dyn Fn(bytes::Bytes) -> Result<Box<dyn BootInfo>, Rejection> + Send +
+                             Sync>;
+pub type ScanBootInfo = Box<dyn Fn(bytes::Bytes) -> Result<Vec<Box<dyn BootInfo>>, Rejection> +
+                            Send + Sync>;
 
 pub struct Descriptor {
     pub name: &'static str,
     pub max_range: usize,
     parser: ParseBootInfo,
+    scanner: ScanBootInfo,
 }
 
 impl Descriptor {
-    pub fn parse(&self, buf: bytes::Bytes) -> Option<Box<BootInfo>> {
+    /// Parse the header that a boot loader would use
+    pub fn parse(&self, buf: bytes::Bytes) -> Result<Box<dyn BootInfo>, Rejection> {
         (self.parser)(buf)
     }
+
+    /// Parse every valid header in the data, not just the one that a boot loader would use
+    pub fn parse_all(&self, buf: bytes::Bytes) -> Result<Vec<Box<dyn BootInfo>>, Rejection> {
+        (self.scanner)(buf)
+    }
 }
 
+#[cfg(test)]
+mod builders;
+pub mod elf;
+pub mod fields;
+pub mod ikconfig;
+pub mod kludge;
 pub mod linux;
 pub mod multiboot1;
 pub mod multiboot2;
+pub mod pe;
+pub mod uki;
 
 pub fn register() -> Vec<Descriptor> {
+    register_with(&[])
+}
+
+/// Like `register`, but with `tag_decoders` for the Multiboot2 tag types that the parser does not
+/// know
+pub fn register_with(tag_decoders: &'static [multiboot2::TagDecoder]) -> Vec<Descriptor> {
     let mut descs = vec![];
     linux::register(&mut descs);
     multiboot1::register(&mut descs);
-    multiboot2::register(&mut descs);
+    multiboot2::register(&mut descs, tag_decoders);
+    pe::register(&mut descs);
     descs
 }
@@ -1,3 +1,6 @@
+use super::elf::{Elf, Segment};
+use super::kludge::Kludge;
+use super::{Field, Rejection, Value};
 use bytes::{Buf, IntoBuf};
 use std::fmt;
 pub const MAGIC: u32 = 0x1BADB002;
@@ -12,8 +15,9 @@ bitflags! {
         }
     }
 
-impl fmt::Display for Flags {
-    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+impl Flags {
+    /// The names of the flags that are set
+    pub fn names(&self) -> Vec<&'static str> {
         let mut flags = vec![];
 
         if self.contains(Flags::PAGE_ALIGNED_MODULES) {
@@ -32,7 +36,13 @@ impl fmt::Display for Flags {
             flags.push("entry-addrs-valid");
         }
 
-        write!(f, "[{}]", flags.join(", "))
+        flags
+    }
+}
+
+impl fmt::Display for Flags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "[{}]", self.names().join(", "))
     }
 }
 
@@ -41,8 +51,25 @@ pub const GRAPHICS_MODE_TYPE_EGA_TEXT: u32 = 1;
 
 pub const GRAPHICS_NO_PREFERENCE: u32 = 0;
 
+/// The header must be contained completely within this many bytes of the start of the image
+pub const SEARCH_LIMIT: usize = 8192;
+/// The alignment that the header must have within the image, a power of two
+pub const ALIGNMENT: usize = 4;
+
+const PT_LOAD: u32 = 1;
+
+/// How a boot loader finds the layout of an image without the address fields
+#[derive(Clone, Debug)]
+enum ElfLayout {
+    Elf(Elf),
+    NotElf,
+    Invalid(String),
+}
+
 #[derive(Debug)]
 pub struct Header {
+    offset: usize,
+    selected: bool,
     magic: u32,
     flags: Flags,
     checksum: u32,
@@ -55,106 +82,389 @@ pub struct Header {
     width: u32,
     height: u32,
     depth: u32,
+    elf: Option<ElfLayout>,
 }
 
-impl super::BootInfo for Header {}
+impl super::BootInfo for Header {
+    fn format(&self) -> &'static str {
+        "multiboot1"
+    }
+
+    fn title(&self) -> &'static str {
+        "Multiboot Header"
+    }
+
+    fn fields(&self) -> Vec<Field> {
+        let mut fields = vec![Field::new("offset", "Offset", Value::offset(self.offset)),
+                              Field::new("selected", "Selected", Value::Bool(self.selected)),
+                              Field::new("magic", "Magic", Value::hex32(self.magic)),
+                              Field::new("flags",
+                                         "Flags",
+                                         Value::Flags {
+                                             bits: u64::from(self.flags.bits()),
+                                             digits: 8,
+                                             names: self.flags.names(),
+                                         }),
+                              Field::new("checksum", "Checksum", Value::hex32(self.checksum))];
+
+        if self.flags.contains(Flags::ENTRY_ADDRS_VALID) {
+            fields.push(Field::new("header_addr", "Header", Value::hex32(self.header_addr)));
+            fields.push(Field::new("load_addr", "Load", Value::hex32(self.load_addr)));
+            fields.push(Field::new("load_end_addr", "Load End", Value::hex32(self.load_end_addr)));
+            fields.push(Field::new("bss_end_addr", "BSS End", Value::hex32(self.bss_end_addr)));
+            fields.push(Field::new("entry_addr", "Entry", Value::hex32(self.entry_addr)));
+            if let Some(offset) = self.kludge().load_offset() {
+                fields.push(Field::new("load_offset", "Load Offset", Value::offset(offset)));
+            }
+        } else if let Some(ElfLayout::Elf(ref elf)) = self.elf {
+            fields.extend(elf_fields(elf));
+        }
+
+        if self.flags.contains(Flags::REQUEST_VIDEO_MODE) {
+            let mode = match self.mode_type {
+                GRAPHICS_MODE_TYPE_LINEAR => "linear",
+                GRAPHICS_MODE_TYPE_EGA_TEXT => "ega",
+                _ => "unknown",
+            };
+
+            let preference = |x| match x {
+                GRAPHICS_NO_PREFERENCE => Value::Text("no preference".into()),
+                x => Value::Integer(u64::from(x)),
+            };
+
+            fields.push(Field::new("mode_type",
+                                   "Mode",
+                                   Value::Enum {
+                                       value: u64::from(self.mode_type),
+                                       name: mode,
+                                   }));
+            fields.push(Field::new("width", "Width", preference(self.width)));
+            fields.push(Field::new("height", "Height", preference(self.height)));
+            fields.push(Field::new("depth", "Depth", preference(self.depth)));
+        }
+
+        fields
+    }
+
+    fn warnings(&self) -> Vec<String> {
+        let mut warnings = vec![];
+
+        if self.misaligned() {
+            warnings.push(format!("header is misplaced: offset 0x{:x} is not {}-byte aligned, so \
+                                   boot loaders will not find it",
+                                  self.offset,
+                                  ALIGNMENT));
+        }
+        if self.past_search_limit() {
+            warnings.push(format!("header is misplaced: it ends past the first {} bytes of the \
+                                   image, so boot loaders will not find it",
+                                  SEARCH_LIMIT));
+        }
+
+        if self.flags.contains(Flags::ENTRY_ADDRS_VALID) {
+            warnings.extend(self.kludge().violations());
+        }
+
+        match self.elf {
+            Some(ElfLayout::Elf(ref elf)) => {
+                if load_segments(elf).next().is_none() {
+                    warnings.push("ELF image has no loadable segments".into());
+                } else if elf_entry(elf).is_none() {
+                    warnings.push(format!("ELF entry point 0x{:x} is outside the loadable \
+                                           segments",
+                                          elf.entry()));
+                }
+            }
+            Some(ElfLayout::NotElf) => {
+                warnings.push("image is not ELF and the header has no address fields, so boot \
+                               loaders cannot load it"
+                                      .into())
+            }
+            Some(ElfLayout::Invalid(ref reason)) => {
+                warnings.push(format!("ELF image cannot be loaded: {}", reason))
+            }
+            None => {}
+        }
+
+        warnings
+    }
+}
 
 impl Header {
-    pub fn parse(buf: ::bytes::Bytes) -> Option<Box<super::BootInfo>> {
-        let mut buf = buf.into_buf();
-        while buf.remaining() > ::std::mem::size_of::<u32>() {
-            let value = buf.get_u32::<::bytes::LittleEndian>();
-            if value == MAGIC {
-                break;
+    pub fn parse(buf: ::bytes::Bytes) -> Result<Header, Rejection> {
+        let mut headers = Header::parse_all(buf)?;
+        // Fall back to the first misplaced header when there is nothing a boot loader would use
+        let index = headers.iter().position(|h| h.selected).unwrap_or(0);
+        Ok(headers.swap_remove(index))
+    }
+
+    /// Find every valid header in the data, marking the one that a boot loader would use as
+    /// selected
+    pub fn parse_all(buf: ::bytes::Bytes) -> Result<Vec<Header>, Rejection> {
+        let mut rejection = Rejection::NotPresent;
+        let mut headers = vec![];
+        // Without the address fields, a boot loader loads the image by its ELF program headers
+        let elf = match Elf::parse(&buf) {
+            Ok(elf) => ElfLayout::Elf(elf),
+            Err(Rejection::NotPresent) => ElfLayout::NotElf,
+            Err(Rejection::Invalid { reason, .. }) => ElfLayout::Invalid(reason),
+        };
+        for offset in super::find_magic(&buf, MAGIC) {
+            // Keep looking past a bad candidate, but remember why the first one failed
+            match Header::parse_at(&buf, offset) {
+                Ok(mut header) => {
+                    if !header.flags.contains(Flags::ENTRY_ADDRS_VALID) {
+                        header.elf = Some(elf.clone());
+                    }
+                    headers.push(header)
+                }
+                Err(err) => {
+                    if rejection == Rejection::NotPresent {
+                        rejection = err;
+                    }
+                }
             }
         }
 
+        // A boot loader uses the first header it comes across in a place it looks
+        if let Some(header) = headers.iter_mut().find(|h| !h.misplaced()) {
+            header.selected = true;
+        }
+
+        if headers.is_empty() {
+            Err(rejection)
+        } else {
+            Ok(headers)
+        }
+    }
+
+    /// Parse the header whose magic value is at `offset`
+    fn parse_at(buf: &::bytes::Bytes, offset: usize) -> Result<Header, Rejection> {
+        let mut buf = buf.clone().into_buf();
+        buf.set_position((offset + ::std::mem::size_of::<u32>()) as u64);
+
         if buf.remaining() < (::std::mem::size_of::<u32>() * 11) {
-            None
+            Err(Rejection::invalid(offset, "header is truncated"))
         } else {
-            let flags = buf.get_u32::<::bytes::LittleEndian>();
-            let checksum = buf.get_u32::<::bytes::LittleEndian>();
-            if MAGIC.wrapping_add(flags).wrapping_add(checksum) != 0 {
-                return None;
+            let flags = buf.get_u32_le();
+            let checksum = buf.get_u32_le();
+            let sum = MAGIC.wrapping_add(flags).wrapping_add(checksum);
+            if sum != 0 {
+                return Err(Rejection::invalid(offset,
+                                              format!("bad checksum 0x{:08x} (header sums to \
+                                                       0x{:08x} instead of 0)",
+                                                      checksum,
+                                                      sum)));
             }
 
             let header = Header {
+                offset,
+                selected: false,
                 magic: MAGIC,
                 flags: Flags::from_bits_truncate(flags),
-                checksum: checksum,
-                header_addr: buf.get_u32::<::bytes::LittleEndian>(),
-                load_addr: buf.get_u32::<::bytes::LittleEndian>(),
-                load_end_addr: buf.get_u32::<::bytes::LittleEndian>(),
-                bss_end_addr: buf.get_u32::<::bytes::LittleEndian>(),
-                entry_addr: buf.get_u32::<::bytes::LittleEndian>(),
-                mode_type: buf.get_u32::<::bytes::LittleEndian>(),
-                width: buf.get_u32::<::bytes::LittleEndian>(),
-                height: buf.get_u32::<::bytes::LittleEndian>(),
-                depth: buf.get_u32::<::bytes::LittleEndian>(),
+                checksum,
+                header_addr: buf.get_u32_le(),
+                load_addr: buf.get_u32_le(),
+                load_end_addr: buf.get_u32_le(),
+                bss_end_addr: buf.get_u32_le(),
+                entry_addr: buf.get_u32_le(),
+                mode_type: buf.get_u32_le(),
+                width: buf.get_u32_le(),
+                height: buf.get_u32_le(),
+                depth: buf.get_u32_le(),
+                elf: None,
             };
 
-            Some(Box::new(header) as Box<super::BootInfo>)
+            Ok(header)
         }
     }
-}
 
-impl fmt::Display for Header {
-    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        write!(f, "Multiboot Header\n")?;
-        write!(f, "  Magic     : 0x{:08x}\n", self.magic)?;
-        write!(f,
-               "  Flags     : {} (0x{:08x})\n",
-               self.flags,
-               self.flags.bits())?;
-        write!(f, "  Checksum  : 0x{:08x}\n", self.checksum)?;
-        if self.flags.contains(Flags::ENTRY_ADDRS_VALID) {
-            write!(f, "  Header    : 0x{:08x}\n", self.header_addr)?;
-            write!(f, "  Load      : 0x{:08x}\n", self.load_addr)?;
-            write!(f, "  Load End  : 0x{:08x}\n", self.load_end_addr)?;
-            write!(f, "  BSS End   : 0x{:08x}\n", self.bss_end_addr)?;
-        }
+    /// The offset of the header within the (decompressed) data
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The number of bytes of the header that are meaningful given its flags
+    pub fn length(&self) -> usize {
         if self.flags.contains(Flags::REQUEST_VIDEO_MODE) {
-            let mode = match self.mode_type {
-                GRAPHICS_MODE_TYPE_LINEAR => "linear",
-                GRAPHICS_MODE_TYPE_EGA_TEXT => "ega",
-                _ => "unknown",
-            };
+            48
+        } else if self.flags.contains(Flags::ENTRY_ADDRS_VALID) {
+            32
+        } else {
+            12
+        }
+    }
 
-            let width = match self.width {
-                GRAPHICS_NO_PREFERENCE => "no preference".into(),
-                x => format!("{}", x),
-            };
+    /// Whether this is the header that a boot loader would use
+    pub fn selected(&self) -> bool {
+        self.selected
+    }
 
-            let height = match self.height {
-                GRAPHICS_NO_PREFERENCE => "no preference".into(),
-                x => format!("{}", x),
-            };
+    /// Whether a boot loader would skip over the header because of where it is placed
+    pub fn misplaced(&self) -> bool {
+        self.misaligned() || self.past_search_limit()
+    }
 
-            let depth = match self.depth {
-                GRAPHICS_NO_PREFERENCE => "no preference".into(),
-                x => format!("{}", x),
-            };
+    fn misaligned(&self) -> bool {
+        self.offset & (ALIGNMENT - 1) != 0
+    }
+
+    fn past_search_limit(&self) -> bool {
+        self.offset + self.length() > SEARCH_LIMIT
+    }
+
+    /// The magic value that identified the header
+    pub fn magic(&self) -> u32 {
+        self.magic
+    }
 
-            write!(f, "  Mode      : {} ({})\n", mode, self.mode_type)?;
-            write!(f, "  Width     : {}\n", width)?;
-            write!(f, "  Height    : {}\n", height)?;
-            write!(f, "  Depth     : {}\n", depth)?;
+    /// The features requested of the boot loader
+    pub fn flags(&self) -> Flags {
+        self.flags
+    }
+
+    /// The checksum that makes the magic, flags and checksum sum to zero
+    pub fn checksum(&self) -> u32 {
+        self.checksum
+    }
+
+    /// The physical address of the header, only meaningful with `ENTRY_ADDRS_VALID`
+    pub fn header_addr(&self) -> u32 {
+        self.header_addr
+    }
+
+    /// The physical address of the start of the text segment, only meaningful with
+    /// `ENTRY_ADDRS_VALID`
+    pub fn load_addr(&self) -> u32 {
+        self.load_addr
+    }
+
+    /// The physical address of the end of the data segment, only meaningful with
+    /// `ENTRY_ADDRS_VALID`
+    pub fn load_end_addr(&self) -> u32 {
+        self.load_end_addr
+    }
+
+    /// The physical address of the end of the BSS segment, only meaningful with
+    /// `ENTRY_ADDRS_VALID`
+    pub fn bss_end_addr(&self) -> u32 {
+        self.bss_end_addr
+    }
+
+    /// The physical address to jump to, only meaningful with `ENTRY_ADDRS_VALID`
+    pub fn entry_addr(&self) -> u32 {
+        self.entry_addr
+    }
+
+    /// The preferred graphics mode, only meaningful with `REQUEST_VIDEO_MODE`
+    pub fn mode_type(&self) -> u32 {
+        self.mode_type
+    }
+
+    /// The preferred number of columns, only meaningful with `REQUEST_VIDEO_MODE`
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// The preferred number of lines, only meaningful with `REQUEST_VIDEO_MODE`
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The preferred number of bits per pixel, only meaningful with `REQUEST_VIDEO_MODE`
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// The address fields, only meaningful with `ENTRY_ADDRS_VALID`
+    pub fn kludge(&self) -> Kludge {
+        Kludge {
+            header_offset: self.offset,
+            header_addr: self.header_addr,
+            load_addr: self.load_addr,
+            load_end_addr: self.load_end_addr,
+            bss_end_addr: self.bss_end_addr,
+            entry_addr: Some(self.entry_addr),
+        }
+    }
+
+    /// The ELF image that a boot loader loads, when the address fields are not valid
+    pub fn elf(&self) -> Option<&Elf> {
+        match self.elf {
+            Some(ElfLayout::Elf(ref elf)) => Some(elf),
+            _ => None,
         }
-        Ok(())
+    }
+}
+
+/// The segments that a boot loader copies into memory
+fn load_segments(elf: &Elf) -> impl Iterator<Item = &Segment> {
+    elf.segments()
+        .iter()
+        .filter(|segment| segment.typ() == PT_LOAD && segment.memsz() != 0)
+}
+
+/// The physical address that a boot loader jumps to: the entry point translated through the
+/// segment that contains it, as GRUB does
+fn elf_entry(elf: &Elf) -> Option<u64> {
+    let entry = elf.entry();
+    load_segments(elf)
+        .find(|segment| entry >= segment.vaddr() && entry - segment.vaddr() < segment.memsz())
+        .map(|segment| entry - segment.vaddr() + segment.paddr())
+}
+
+/// The zero-filled memory past the file contents of the loadable segments
+fn elf_bss_size(elf: &Elf) -> u64 {
+    load_segments(elf)
+        .map(|segment| segment.memsz().saturating_sub(segment.filesz()))
+        .sum()
+}
+
+fn elf_fields(elf: &Elf) -> Vec<Field> {
+    let mut fields = vec![elf.class_field(),
+                          Field::new("elf_entry", "ELF Entry", elf.address(elf.entry()))];
+    if let Some(entry) = elf_entry(elf) {
+        fields.push(Field::new("entry_paddr", "Entry Phys", elf.address(entry)));
+    }
+    fields.push(Field::new("bss_size", "BSS Size", Value::Size(elf_bss_size(elf))));
+    fields.push(Field::new("load_segments",
+                           "Load Segments",
+                           Value::Tags(load_segments(elf)
+                                           .map(|segment| elf.segment_group(segment))
+                                           .collect())));
+    fields
+}
+
+impl fmt::Display for Header {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        super::fields::write_text(self, f)
     }
 }
 
 pub fn register(descs: &mut Vec<super::Descriptor>) {
     descs.push(super::Descriptor {
                    name: "multiboot1",
-                   max_range: 8192,
-                   parser: Header::parse,
+                   // Look past the search limit so that misplaced headers can be reported
+                   max_range: 32768,
+                   parser: Box::new(|buf| {
+                       Header::parse(buf).map(|h| Box::new(h) as Box<dyn super::BootInfo>)
+                   }),
+                   scanner: Box::new(|buf| {
+                       Header::parse_all(buf).map(|headers| {
+                           headers
+                               .into_iter()
+                               .map(|h| Box::new(h) as Box<dyn super::BootInfo>)
+                               .collect()
+                       })
+                   }),
                })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use parsers::builders::elf64;
+    use parsers::BootInfo;
     use std::io;
     use utils;
     const MULTIBOOT1: &[u8; 40000] = include_bytes!("../../test-data/multiboot1");
@@ -167,6 +477,146 @@ mod tests {
         Header::parse(bytes).unwrap();
     }
 
+    #[test]
+    fn fields_of_multiboot1() {
+        let cursor = io::Cursor::new(MULTIBOOT1.as_ref());
+        let bytes = utils::header_bytes(cursor, 8192).unwrap();
+        let fields = Header::parse(bytes).unwrap().fields();
+        let names: Vec<_> = fields.iter().map(|f| f.name).collect();
+        assert_eq!(names,
+                   ["offset", "selected", "magic", "flags", "checksum", "elf_class", "elf_entry",
+                    "entry_paddr", "bss_size", "load_segments"]);
+        assert_eq!(fields[1].value, Value::Bool(true));
+        assert_eq!(fields[2].value, Value::hex32(MAGIC));
+    }
+
+    #[test]
+    fn describe_elf_layout() {
+        let cursor = io::Cursor::new(MULTIBOOT1.as_ref());
+        let bytes = utils::header_bytes(cursor, 32768).unwrap();
+        let header = Header::parse(bytes).unwrap();
+        let elf = header.elf().unwrap();
+        assert_eq!(elf_entry(elf), Some(0x0020_0000));
+        assert_eq!(elf_bss_size(elf), 0x0035_8000 - 0x0021_3080);
+        assert_eq!(load_segments(elf).count(), 1);
+        assert!(header.warnings().is_empty());
+    }
+
+    /// A header with the given flags at offset 0 of `data`
+    fn with_header(mut data: Vec<u8>, flags: Flags) -> Vec<u8> {
+        let checksum = 0u32.wrapping_sub(MAGIC).wrapping_sub(flags.bits());
+        data[0..4].copy_from_slice(&MAGIC.to_le_bytes());
+        data[4..8].copy_from_slice(&flags.bits().to_le_bytes());
+        data[8..12].copy_from_slice(&checksum.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn translate_elf_entry_to_physical() {
+        // Link the loadable segment into the higher half, and put the header after the image
+        let mut data = elf64(0xffff_ffff_8100_0010, &[]);
+        data[64 + 16..64 + 24].copy_from_slice(&0xffff_ffff_8100_0000u64.to_le_bytes());
+        data.extend(with_header(vec![0u8; 48], Flags::empty()));
+        let header = Header::parse(data.into()).unwrap();
+        let elf = header.elf().cloned().unwrap();
+        assert_eq!(elf_entry(&elf), Some(0x0100_0010));
+
+        // The ELF fields have the same shape as for any other ELF image
+        let fields = header.fields();
+        let class = fields.iter().find(|f| f.name == "elf_class").unwrap();
+        assert_eq!(class.value.to_json(), json!({ "value": 2, "name": "ELF64" }));
+        let segments = fields.iter().find(|f| f.name == "load_segments").unwrap();
+        assert_eq!(segments.value.to_json()[0]["title"], "Segment: LOAD");
+    }
+
+    #[test]
+    fn warn_about_unloadable_image() {
+        let header = Header::parse(with_header(vec![0u8; 64], Flags::empty()).into()).unwrap();
+        assert!(header.elf().is_none());
+        assert_eq!(header.warnings().len(), 1);
+
+        // The address fields take the place of the ELF headers
+        let mut data = with_header(vec![0u8; 64], Flags::ENTRY_ADDRS_VALID);
+        data[12..16].copy_from_slice(&0x0010_0000u32.to_le_bytes());
+        data[16..20].copy_from_slice(&0x0010_0000u32.to_le_bytes());
+        data[28..32].copy_from_slice(&0x0010_0020u32.to_le_bytes());
+        let header = Header::parse(data.into()).unwrap();
+        assert!(header.warnings().is_empty());
+    }
+
+    #[test]
+    fn cross_check_address_fields() {
+        // A header 0x20 bytes into the file claims loading starts 0x40 bytes before it
+        let mut data = vec![0u8; 0x20];
+        let mut header = with_header(vec![0u8; 64], Flags::ENTRY_ADDRS_VALID);
+        for (index, addr) in [0x0010_0040u32, 0x0010_0000, 0x0010_1000, 0x0010_0800, 0x0020_0000]
+                .iter()
+                .enumerate() {
+            header[12 + index * 4..16 + index * 4].copy_from_slice(&addr.to_le_bytes());
+        }
+        data.extend(header);
+        let header = Header::parse(data.into()).unwrap();
+        assert_eq!(header.kludge().load_offset(), None);
+        assert!(!header.fields().iter().any(|f| f.name == "load_offset"));
+        let warnings = header.warnings();
+        assert_eq!(warnings.len(), 3, "{:?}", warnings);
+        assert!(warnings[0].starts_with("loading starts"));
+        assert!(warnings[1].starts_with("bss_end_addr"));
+        assert!(warnings[2].starts_with("entry_addr"));
+    }
+
+    #[test]
+    fn diagnose_bad_checksum() {
+        let mut data = vec![0u8; 64];
+        data[8..12].copy_from_slice(&MAGIC.to_le_bytes());
+        match Header::parse(data.into()) {
+            Err(Rejection::Invalid { offset, reason }) => {
+                assert_eq!(offset, 8);
+                assert!(reason.starts_with("bad checksum"));
+            }
+            other => panic!("unexpected outcome {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_header_past_search_limit() {
+        let mut data = vec![0u8; SEARCH_LIMIT + 64];
+        let checksum = 0u32.wrapping_sub(MAGIC);
+        data[SEARCH_LIMIT..SEARCH_LIMIT + 4].copy_from_slice(&MAGIC.to_le_bytes());
+        data[SEARCH_LIMIT + 8..SEARCH_LIMIT + 12].copy_from_slice(&checksum.to_le_bytes());
+        let header = Header::parse(data.into()).unwrap();
+        assert_eq!(header.offset(), SEARCH_LIMIT);
+        assert!(header.misplaced());
+        // The zeroed image is not ELF either, so nothing could load it
+        assert_eq!(header.warnings().len(), 2);
+        assert!(!header.selected());
+    }
+
+    #[test]
+    fn parse_all_candidates() {
+        let mut data = vec![0u8; 96];
+        let checksum = 0u32.wrapping_sub(MAGIC);
+        // A stray magic value without a valid checksum is not a candidate
+        data[0..4].copy_from_slice(&MAGIC.to_le_bytes());
+        for &offset in &[16, 40] {
+            data[offset..offset + 4].copy_from_slice(&MAGIC.to_le_bytes());
+            data[offset + 8..offset + 12].copy_from_slice(&checksum.to_le_bytes());
+        }
+        let headers = Header::parse_all(data.clone().into()).unwrap();
+        let offsets: Vec<usize> = headers.iter().map(|h| h.offset()).collect();
+        assert_eq!(offsets, vec![16, 40]);
+        assert!(headers[0].selected());
+        assert!(!headers[1].selected());
+        assert_eq!(Header::parse(data.into()).unwrap().offset(), 16);
+    }
+
+    #[test]
+    fn diagnose_not_present() {
+        let cursor = io::Cursor::new(LINUXBOOT.as_ref());
+        let bytes = utils::header_bytes(cursor, 8192).unwrap();
+        assert_eq!(Header::parse(bytes).unwrap_err(), Rejection::NotPresent);
+    }
+
     #[test]
     #[should_panic]
     fn parse_invalid_linuxboot() {
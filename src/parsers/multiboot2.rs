@@ -1,8 +1,16 @@
+use super::kludge::Kludge;
+use super::{Field, Group, Rejection, TryBuf, Value};
 use bytes::{self, Buf, IntoBuf};
 use std::fmt;
+use std::io::Cursor;
 use std::mem::size_of;
 pub const MAGIC: u32 = 0xE85250D6;
 
+/// The header must be contained completely within this many bytes of the start of the image
+pub const SEARCH_LIMIT: usize = 32768;
+/// The alignment that the header must have within the image, a power of two
+pub const ALIGNMENT: usize = 8;
+
 bitflags! {
         pub struct Flags: u16 {
             const OPTIONAL      = 0x0001;
@@ -10,8 +18,22 @@ bitflags! {
         }
     }
 
+bitflags! {
+        pub struct ConsoleFlags: u32 {
+            const CONSOLE_REQUIRED   = 0x0000_0001;
+            const EGA_TEXT_SUPPORTED = 0x0000_0002;
+        }
+    }
+
+pub const ARCHITECTURE_I386: u32 = 0;
+pub const ARCHITECTURE_MIPS32: u32 = 4;
+
+pub const GRAPHICS_NO_PREFERENCE: u32 = 0;
+
 #[derive(Debug)]
 pub struct Header {
+    offset: usize,
+    selected: bool,
     magic: u32,
     architecture: u32,
     header_length: u32,
@@ -31,6 +53,37 @@ const TAG_EFI_I386_ENTRY_ADDRESS: u16 = 8;
 const TAG_EFI_AMD64_ENTRY_ADDRESS: u16 = 9;
 const TAG_RELOCATABLE: u16 = 10;
 
+/// The name of a boot information tag type that an `Information Request` tag can ask for
+pub fn mbi_tag_name(typ: u32) -> Option<&'static str> {
+    Some(match typ {
+             1 => "boot command line",
+             2 => "boot loader name",
+             3 => "modules",
+             4 => "basic meminfo",
+             5 => "bootdev",
+             6 => "mmap",
+             7 => "VBE",
+             8 => "framebuffer",
+             9 => "ELF sections",
+             10 => "APM",
+             11 => "EFI32 system table",
+             12 => "EFI64 system table",
+             13 => "SMBIOS",
+             14 => "ACPI old",
+             15 => "ACPI new",
+             16 => "network",
+             17 => "EFI mmap",
+             18 => "EFI BS not terminated",
+             19 => "EFI32 image handle",
+             20 => "EFI64 image handle",
+             21 => "load base address",
+             _ => return None,
+         })
+}
+
+/// The `load_addr` of an `Address` tag that loads the image from the start of the file
+pub const LOAD_FROM_START: u32 = 0xffff_ffff;
+
 #[derive(Debug)]
 pub enum TagVariant {
     InformationRequest { mbi_tag_types: Vec<u32> },
@@ -53,213 +106,519 @@ pub enum TagVariant {
         align: u32,
         preference: u32,
     },
-    Unknown,
+    /// A tag of a type that a `TagDecoder` was given for
+    Custom {
+        name: &'static str,
+        fields: Vec<Field>,
+    },
+    /// A tag of a type that nothing knows how to decode, with its payload
+    Unknown { data: bytes::Bytes },
+}
+
+/// Decode the payload of a tag into fields, or explain why it is not valid
+pub type DecodeTag = fn(&[u8]) -> Result<Vec<Field>, String>;
+
+/// A decoder for a tag type that the Multiboot2 specification does not define, such as an
+/// experimental or vendor-specific tag
+///
+/// Decoders are only consulted for types that the parser does not know itself.
+#[derive(Clone, Copy)]
+pub struct TagDecoder {
+    pub typ: u16,
+    pub name: &'static str,
+    pub decode: DecodeTag,
 }
 
 #[derive(Debug)]
 pub struct Tag {
+    offset: usize,
     typ: u16,
     flags: Flags,
     size: u32,
     variant: TagVariant,
+    /// Why the `TagDecoder` for the tag type could not decode it
+    decode_error: Option<String>,
 }
 
 fn ending_tag(typ: u16, size: u32) -> bool {
-    (typ == TAG_ENDING && size == 8)
+    typ == TAG_ENDING && size == 8
 }
 
-impl super::BootInfo for Header {}
+impl super::BootInfo for Header {
+    fn format(&self) -> &'static str {
+        "multiboot2"
+    }
+
+    fn title(&self) -> &'static str {
+        "Multiboot2 Header"
+    }
+
+    fn fields(&self) -> Vec<Field> {
+        let tags = self.tags
+            .iter()
+            .map(|tag| {
+                     let mut fields = tag.fields();
+                     if let TagVariant::Address { .. } = tag.variant {
+                         if let Some(offset) = self.kludge().and_then(|k| k.load_offset()) {
+                             fields.push(Field::new("load_offset",
+                                                    "Load Offset",
+                                                    Value::offset(offset)));
+                         }
+                     }
+                     Group {
+                         title: format!("Tag: {} ({})", tag.variant, tag.typ),
+                         fields,
+                     }
+                 })
+            .collect();
+
+        vec![Field::new("offset", "Offset", Value::offset(self.offset)),
+             Field::new("selected", "Selected", Value::Bool(self.selected)),
+             Field::new("magic", "Magic", Value::hex32(self.magic)),
+             Field::new("architecture",
+                        "Arch",
+                        Value::Enum {
+                            value: u64::from(self.architecture),
+                            name: match self.architecture {
+                                ARCHITECTURE_I386 => "i386",
+                                ARCHITECTURE_MIPS32 => "MIPS32",
+                                _ => "unknown",
+                            },
+                        }),
+             Field::new("header_length", "Header Len", Value::hex32(self.header_length)),
+             Field::new("checksum", "Checksum", Value::hex32(self.checksum)),
+             Field::new("tags", "Tags", Value::Tags(tags))]
+    }
+
+    fn warnings(&self) -> Vec<String> {
+        let mut warnings = vec![];
+
+        if self.misaligned() {
+            warnings.push(format!("header is misplaced: offset 0x{:x} is not {}-byte aligned, so \
+                                   boot loaders will not find it",
+                                  self.offset,
+                                  ALIGNMENT));
+        }
+        if self.past_search_limit() {
+            warnings.push(format!("header is misplaced: it ends past the first {} bytes of the \
+                                   image, so boot loaders will not find it",
+                                  SEARCH_LIMIT));
+        }
+
+        if let Some(kludge) = self.kludge() {
+            warnings.extend(kludge.violations());
+        }
+
+        warnings.extend(self.tags.iter().filter_map(|tag| tag.decode_error.clone()));
+
+        // GRUB refuses to boot a kernel that requires information it does not know how to give
+        for tag in self.tags.iter().filter(|tag| !tag.flags.contains(Flags::OPTIONAL)) {
+            if let TagVariant::InformationRequest { ref mbi_tag_types } = tag.variant {
+                for &typ in mbi_tag_types.iter().filter(|&&typ| mbi_tag_name(typ).is_none()) {
+                    warnings.push(format!("required information request tag at 0x{:x} asks \
+                                           for unknown type {}, so boot loaders will refuse to \
+                                           boot the image",
+                                          tag.offset,
+                                          typ));
+                }
+            }
+        }
+
+        warnings
+    }
+}
 
 impl Header {
-    pub fn parse(buf: bytes::Bytes) -> Option<Box<super::BootInfo>> {
-        let mut buf = buf.into_buf();
-        while buf.remaining() > size_of::<u32>() {
-            let value = buf.get_u32::<bytes::LittleEndian>();
-            if value == MAGIC {
-                break;
+    pub fn parse(buf: bytes::Bytes) -> Result<Header, Rejection> {
+        Header::parse_with(buf, &[])
+    }
+
+    /// Like `parse`, but decoding the tags that `decoders` are given for
+    pub fn parse_with(buf: bytes::Bytes, decoders: &[TagDecoder]) -> Result<Header, Rejection> {
+        let mut headers = Header::parse_all_with(buf, decoders)?;
+        // Fall back to the first misplaced header when there is nothing a boot loader would use
+        let index = headers.iter().position(|h| h.selected).unwrap_or(0);
+        Ok(headers.swap_remove(index))
+    }
+
+    /// Find every valid header in the data, marking the one that a boot loader would use as
+    /// selected
+    pub fn parse_all(buf: bytes::Bytes) -> Result<Vec<Header>, Rejection> {
+        Header::parse_all_with(buf, &[])
+    }
+
+    /// Like `parse_all`, but decoding the tags that `decoders` are given for
+    pub fn parse_all_with(buf: bytes::Bytes,
+                          decoders: &[TagDecoder])
+                          -> Result<Vec<Header>, Rejection> {
+        let mut rejection = Rejection::NotPresent;
+        let mut headers = vec![];
+        for offset in super::find_magic(&buf, MAGIC) {
+            // Keep looking past a bad candidate, but remember why the first one failed
+            match Header::parse_at(&buf, offset, decoders) {
+                Ok(header) => headers.push(header),
+                Err(err) => {
+                    if rejection == Rejection::NotPresent {
+                        rejection = err;
+                    }
+                }
             }
         }
 
+        // A boot loader uses the first header it comes across in a place it looks
+        if let Some(header) = headers.iter_mut().find(|h| !h.misplaced()) {
+            header.selected = true;
+        }
+
+        if headers.is_empty() {
+            Err(rejection)
+        } else {
+            Ok(headers)
+        }
+    }
+
+    /// Parse the header whose magic value is at `offset`
+    fn parse_at(buf: &bytes::Bytes,
+                offset: usize,
+                decoders: &[TagDecoder])
+                -> Result<Header, Rejection> {
+        let mut buf = buf.clone().into_buf();
+        buf.set_position((offset + size_of::<u32>()) as u64);
+
         if buf.remaining() < (size_of::<u32>() * 3) {
-            return None;
+            return Err(Rejection::invalid(offset, "header is truncated"));
+        }
+
+        let architecture = buf.try_get_u32_le()?;
+        let header_length = buf.try_get_u32_le()?;
+        let checksum = buf.try_get_u32_le()?;
+        let sum = MAGIC
+            .wrapping_add(architecture)
+            .wrapping_add(header_length)
+            .wrapping_add(checksum);
+        if sum != 0 {
+            return Err(Rejection::invalid(offset,
+                                          format!("bad checksum 0x{:08x} (header sums to \
+                                                   0x{:08x} instead of 0)",
+                                                  checksum,
+                                                  sum)));
         }
 
-        let architecture = buf.get_u32::<bytes::LittleEndian>();
-        let header_length = buf.get_u32::<bytes::LittleEndian>();
-        let checksum = buf.get_u32::<bytes::LittleEndian>();
-        if MAGIC
-               .wrapping_add(architecture)
-               .wrapping_add(header_length)
-               .wrapping_add(checksum) != 0 {
-            return None;
+        if header_length as usize > buf.remaining() + (size_of::<u32>() * 4) {
+            return Err(Rejection::invalid(offset,
+                                          format!("header length 0x{:08x} runs past the end \
+                                                   of the data",
+                                                  header_length)));
         }
 
-        if buf.remaining() < (header_length as usize - (size_of::<u32>() * 4)) {
-            return None;
+        if (header_length as usize) < (size_of::<u32>() * 6) {
+            return Err(Rejection::invalid(offset,
+                                          format!("header length 0x{:08x} leaves no room for \
+                                                   the ending tag",
+                                                  header_length)));
         }
 
-        let mut typ = buf.get_u16::<bytes::LittleEndian>();
-        let mut flags = buf.get_u16::<bytes::LittleEndian>();
-        let mut size = buf.get_u32::<bytes::LittleEndian>();
+        let header_end = offset + header_length as usize;
+        let mut tag_offset = buf.position() as usize;
+        let mut typ = buf.try_get_u16_le()?;
+        let mut flags = buf.try_get_u16_le()?;
+        let mut size = buf.try_get_u32_le()?;
         let mut tags = vec![];
 
         while !ending_tag(typ, size) {
+            if (size as usize) < (size_of::<u32>() * 2) {
+                return Err(Rejection::invalid(tag_offset,
+                                              format!("tag type {} has size {}, smaller than \
+                                                       the tag header",
+                                                      typ,
+                                                      size)));
+            }
+            if size as usize > header_end.saturating_sub(tag_offset) {
+                return Err(Rejection::invalid(tag_offset,
+                                              format!("tag type {} with size {} runs past the \
+                                                       end of the header",
+                                                      typ,
+                                                      size)));
+            }
+
             let read_more = size as usize - (size_of::<u32>() * 2);
-            let padding = if read_more % 8 != 0 {
-                ((read_more + 8) % 8)
-            } else {
-                0
-            };
+            let padding = (8 - read_more % 8) % 8;
+            buf.need(read_more)?;
 
-            let variant = match typ {
-                TAG_INFORMATION_REQUEST => {
-                    let mut info = vec![];
-                    for _ in 0..(read_more / size_of::<u32>()) {
-                        info.push(buf.get_u32::<bytes::LittleEndian>());
-                    }
-                    TagVariant::InformationRequest { mbi_tag_types: info }
-                }
-                TAG_ADDRESS => {
-                    let header = buf.get_u32::<bytes::LittleEndian>();
-                    let load = buf.get_u32::<bytes::LittleEndian>();
-                    let load_end = buf.get_u32::<bytes::LittleEndian>();
-                    let bss_end = buf.get_u32::<bytes::LittleEndian>();
-                    TagVariant::Address {
-                        header_addr: header,
-                        load_addr: load,
-                        load_end_addr: load_end,
-                        bss_end_addr: bss_end,
-                    }
-                }
-                TAG_ENTRY_ADDRESS => {
-                    let entry = buf.get_u32::<bytes::LittleEndian>();
-                    TagVariant::Entry { entry_addr: entry }
-                }
-                TAG_FLAGS => {
-                    let flags = buf.get_u32::<bytes::LittleEndian>();
-                    TagVariant::Flags { console_flags: flags }
-                }
-                TAG_FRAMEBUFFER => {
-                    let width = buf.get_u32::<bytes::LittleEndian>();
-                    let height = buf.get_u32::<bytes::LittleEndian>();
-                    let depth = buf.get_u32::<bytes::LittleEndian>();
-                    TagVariant::Framebuffer {
-                        width: width,
-                        height: height,
-                        depth: depth,
-                    }
-                }
-                TAG_MODULE_ALIGNMENT => TagVariant::ModuleAlignment,
-                TAG_EFI_BOOT_SERVICES => TagVariant::EfiBootServices,
-                TAG_EFI_I386_ENTRY_ADDRESS => {
-                    let entry = buf.get_u32::<bytes::LittleEndian>();
-                    TagVariant::EfiI386Entry { entry_addr: entry }
-                }
-                TAG_EFI_AMD64_ENTRY_ADDRESS => {
-                    let entry = buf.get_u32::<bytes::LittleEndian>();
-                    TagVariant::EfiAmd64Entry { entry_addr: entry }
-                }
-                TAG_RELOCATABLE => {
-                    let min_addr = buf.get_u32::<bytes::LittleEndian>();
-                    let max_addr = buf.get_u32::<bytes::LittleEndian>();
-                    let align = buf.get_u32::<bytes::LittleEndian>();
-                    let preference = buf.get_u32::<bytes::LittleEndian>();
-
-                    TagVariant::Relocatable {
-                        min_addr: min_addr,
-                        max_addr: max_addr,
-                        align: align,
-                        preference: preference,
+            let start = buf.position() as usize;
+            let mut payload = buf.get_ref().slice(start, start + read_more).into_buf();
+            let variant = Tag::parse_variant(typ, &mut payload).map_err(|_| {
+                    Rejection::invalid(tag_offset,
+                                       format!("tag type {} has size {}, too small for its \
+                                                contents",
+                                               typ,
+                                               size))
+                })?;
+            // A tag that its decoder cannot make sense of is still shown, as an unknown one
+            let mut decode_error = None;
+            let variant = match (variant, decoders.iter().find(|d| d.typ == typ)) {
+                (TagVariant::Unknown { data }, Some(decoder)) => {
+                    match (decoder.decode)(&data) {
+                        Ok(fields) => {
+                            TagVariant::Custom {
+                                name: decoder.name,
+                                fields,
+                            }
+                        }
+                        Err(reason) => {
+                            decode_error = Some(format!("{} tag at 0x{:x} could not be \
+                                                         decoded: {}",
+                                                        decoder.name,
+                                                        tag_offset,
+                                                        reason));
+                            TagVariant::Unknown { data }
+                        }
                     }
                 }
-                _ => {
-                    buf.advance(read_more);
-                    TagVariant::Unknown
-                }
+                (variant, _) => variant,
             };
+
             tags.push(Tag {
-                          typ: typ,
+                          offset: tag_offset,
+                          typ,
                           flags: Flags::from_bits_truncate(flags),
-                          size: size,
-                          variant: variant,
+                          size,
+                          variant,
+                          decode_error,
                       });
 
-            buf.advance(padding);
+            buf.advance(read_more);
+            buf.try_advance(padding)?;
 
-            typ = buf.get_u16::<bytes::LittleEndian>();
-            flags = buf.get_u16::<bytes::LittleEndian>();
-            size = buf.get_u32::<bytes::LittleEndian>();
+            tag_offset = buf.position() as usize;
+            typ = buf.try_get_u16_le()?;
+            flags = buf.try_get_u16_le()?;
+            size = buf.try_get_u32_le()?;
         }
 
         let header = Header {
+            offset,
+            selected: false,
             magic: MAGIC,
-            architecture: architecture,
-            header_length: header_length,
-            checksum: checksum,
-            tags: tags,
+            architecture,
+            header_length,
+            checksum,
+            tags,
         };
-        Some(Box::new(header) as Box<super::BootInfo>)
+        Ok(header)
     }
-}
 
-impl fmt::Display for Header {
-    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        write!(f, "Multiboot2 Header\n")?;
-        write!(f, "  Magic       : 0x{:08x}\n", self.magic)?;
-        write!(f, "  Arch        : 0x{:08x}\n", self.architecture)?;
-        write!(f, "  Header Len  : 0x{:08x}\n", self.header_length)?;
-        write!(f, "  Checksum    : 0x{:08x}\n", self.checksum)?;
-        for x in &self.tags {
-            write!(f, "{}", x)?;
-        }
-        Ok(())
+    /// The offset of the header within the (decompressed) data
+    pub fn offset(&self) -> usize {
+        self.offset
     }
-}
 
-impl fmt::Display for TagVariant {
-    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        let s = match *self {
-            TagVariant::InformationRequest { .. } => "Information Request",
-            TagVariant::Address { .. } => "Address",
-            TagVariant::Entry { .. } => "Entry",
-            TagVariant::EfiI386Entry { .. } => "EFI i386 Entry",
-            TagVariant::EfiAmd64Entry { .. } => "EFI amd64 Entry",
-            TagVariant::Flags { .. } => "Flags",
-            TagVariant::Framebuffer { .. } => "Framebuffer",
-            TagVariant::EfiBootServices => "EFI Boot Services",
-            TagVariant::ModuleAlignment => "Module Alignment",
-            TagVariant::Relocatable { .. } => "Relocatable",
-            TagVariant::Unknown => "Unknown",
-        };
-        write!(f, "{}", s)
+    /// Whether this is the header that a boot loader would use
+    pub fn selected(&self) -> bool {
+        self.selected
     }
-}
 
-impl fmt::Display for Flags {
-    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        let mut flags = vec![];
+    /// Whether a boot loader would skip over the header because of where it is placed
+    pub fn misplaced(&self) -> bool {
+        self.misaligned() || self.past_search_limit()
+    }
 
-        if self.contains(Flags::OPTIONAL) {
-            flags.push("optional");
-        } else {
-            flags.push("required");
-        }
+    fn misaligned(&self) -> bool {
+        self.offset & (ALIGNMENT - 1) != 0
+    }
+
+    fn past_search_limit(&self) -> bool {
+        self.offset + self.header_length as usize > SEARCH_LIMIT
+    }
 
-        write!(f, "[{}]", flags.join(", "))
+    /// The magic value that identified the header
+    pub fn magic(&self) -> u32 {
+        self.magic
+    }
+
+    /// The CPU instruction set architecture the image targets
+    pub fn architecture(&self) -> u32 {
+        self.architecture
+    }
+
+    /// The length of the header in bytes, including all tags
+    pub fn header_length(&self) -> u32 {
+        self.header_length
+    }
+
+    /// The checksum that makes the magic, architecture, length and checksum sum to zero
+    pub fn checksum(&self) -> u32 {
+        self.checksum
+    }
+
+    /// The tags that followed the fixed part of the header, excluding the ending tag
+    pub fn tags(&self) -> &[Tag] {
+        &self.tags
+    }
+
+    /// The load addresses from the `Address` tag, with the entry point from the `Entry` tag
+    pub fn kludge(&self) -> Option<Kludge> {
+        let entry_addr = self.tags
+            .iter()
+            .filter_map(|tag| match tag.variant {
+                            TagVariant::Entry { entry_addr } => Some(entry_addr),
+                            _ => None,
+                        })
+            .next();
+        self.tags
+            .iter()
+            .filter_map(|tag| match tag.variant {
+                            TagVariant::Address {
+                                header_addr,
+                                load_addr,
+                                load_end_addr,
+                                bss_end_addr,
+                            } => {
+                                // Loading from the start of the file puts the header at its offset
+                                let load_addr = if load_addr == LOAD_FROM_START {
+                                    header_addr.checked_sub(self.offset as u32)
+                                        .unwrap_or(load_addr)
+                                } else {
+                                    load_addr
+                                };
+                                Some(Kludge {
+                                         header_offset: self.offset,
+                                         header_addr,
+                                         load_addr,
+                                         load_end_addr,
+                                         bss_end_addr,
+                                         entry_addr,
+                                     })
+                            }
+                            _ => None,
+                        })
+            .next()
     }
 }
 
-impl fmt::Display for Tag {
-    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        write!(f, "  Tag: {} ({})\n", self.variant, self.typ)?;
-        write!(f,
-               "    Flags      : {} (0x{:04x})\n",
-               self.flags,
-               self.flags.bits())?;
-        write!(f, "    Size       : {} bytes\n", self.size)?;
+impl Tag {
+    /// The offset of the tag within the (decompressed) data
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The raw tag type
+    pub fn typ(&self) -> u16 {
+        self.typ
+    }
+
+    /// The flags of the tag
+    pub fn flags(&self) -> Flags {
+        self.flags
+    }
+
+    /// The size of the tag in bytes, excluding any padding
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// The decoded contents of the tag
+    pub fn variant(&self) -> &TagVariant {
+        &self.variant
+    }
+
+    /// Why the `TagDecoder` for the tag type could not decode it, leaving it unknown
+    pub fn decode_error(&self) -> Option<&str> {
+        self.decode_error.as_deref()
+    }
+
+    /// Decode the contents of a tag of type `typ` from exactly the bytes of its payload
+    fn parse_variant(typ: u16, buf: &mut Cursor<bytes::Bytes>) -> Result<TagVariant, Rejection> {
+        let variant = match typ {
+            TAG_INFORMATION_REQUEST => {
+                let mut info = vec![];
+                for _ in 0..(buf.remaining() / size_of::<u32>()) {
+                    info.push(buf.try_get_u32_le()?);
+                }
+                TagVariant::InformationRequest { mbi_tag_types: info }
+            }
+            TAG_ADDRESS => {
+                let header = buf.try_get_u32_le()?;
+                let load = buf.try_get_u32_le()?;
+                let load_end = buf.try_get_u32_le()?;
+                let bss_end = buf.try_get_u32_le()?;
+                TagVariant::Address {
+                    header_addr: header,
+                    load_addr: load,
+                    load_end_addr: load_end,
+                    bss_end_addr: bss_end,
+                }
+            }
+            TAG_ENTRY_ADDRESS => {
+                let entry = buf.try_get_u32_le()?;
+                TagVariant::Entry { entry_addr: entry }
+            }
+            TAG_FLAGS => {
+                let flags = buf.try_get_u32_le()?;
+                TagVariant::Flags { console_flags: flags }
+            }
+            TAG_FRAMEBUFFER => {
+                let width = buf.try_get_u32_le()?;
+                let height = buf.try_get_u32_le()?;
+                let depth = buf.try_get_u32_le()?;
+                TagVariant::Framebuffer {
+                    width,
+                    height,
+                    depth,
+                }
+            }
+            TAG_MODULE_ALIGNMENT => TagVariant::ModuleAlignment,
+            TAG_EFI_BOOT_SERVICES => TagVariant::EfiBootServices,
+            TAG_EFI_I386_ENTRY_ADDRESS => {
+                let entry = buf.try_get_u32_le()?;
+                TagVariant::EfiI386Entry { entry_addr: entry }
+            }
+            TAG_EFI_AMD64_ENTRY_ADDRESS => {
+                let entry = buf.try_get_u32_le()?;
+                TagVariant::EfiAmd64Entry { entry_addr: entry }
+            }
+            TAG_RELOCATABLE => {
+                let min_addr = buf.try_get_u32_le()?;
+                let max_addr = buf.try_get_u32_le()?;
+                let align = buf.try_get_u32_le()?;
+                let preference = buf.try_get_u32_le()?;
+
+                TagVariant::Relocatable {
+                    min_addr,
+                    max_addr,
+                    align,
+                    preference,
+                }
+            }
+            _ => TagVariant::Unknown { data: buf.get_ref().clone() },
+        };
+        Ok(variant)
+    }
+
+    /// The fields of the tag, in display order
+    pub fn fields(&self) -> Vec<Field> {
+        let mut fields = vec![Field::new("offset", "Offset", Value::offset(self.offset)),
+                              Field::new("flags",
+                                         "Flags",
+                                         Value::Flags {
+                                             bits: u64::from(self.flags.bits()),
+                                             digits: 4,
+                                             names: self.flags.names(),
+                                         }),
+                              Field::new("size", "Size", Value::Size(u64::from(self.size)))];
+
         match self.variant {
             TagVariant::InformationRequest { ref mbi_tag_types } => {
-                write!(f, "    Types      : {:?}\n", mbi_tag_types)?;
+                let types = mbi_tag_types
+                    .iter()
+                    .map(|&typ| {
+                             Value::Enum {
+                                 value: u64::from(typ),
+                                 name: mbi_tag_name(typ).unwrap_or("unknown"),
+                             }
+                         })
+                    .collect();
+                fields.push(Field::new("mbi_tag_types", "Types", Value::List(types)));
             }
             TagVariant::Address {
                 header_addr,
@@ -267,27 +626,38 @@ impl fmt::Display for Tag {
                 load_end_addr,
                 bss_end_addr,
             } => {
-                write!(f, "    Header     : 0x{:.08x}\n", header_addr)?;
-                write!(f, "    Load       : 0x{:.08x}\n", load_addr)?;
-                write!(f, "    Load End   : 0x{:.08x}\n", load_end_addr)?;
-                write!(f, "    BSS End    : 0x{:.08x}\n", bss_end_addr)?;
+                fields.push(Field::new("header_addr", "Header", Value::hex32(header_addr)));
+                fields.push(Field::new("load_addr", "Load", Value::hex32(load_addr)));
+                fields.push(Field::new("load_end_addr", "Load End", Value::hex32(load_end_addr)));
+                fields.push(Field::new("bss_end_addr", "BSS End", Value::hex32(bss_end_addr)));
             }
             TagVariant::Entry { entry_addr } |
             TagVariant::EfiI386Entry { entry_addr } |
             TagVariant::EfiAmd64Entry { entry_addr } => {
-                write!(f, "    Entry      : 0x{:.08x}\n", entry_addr)?;
+                fields.push(Field::new("entry_addr", "Entry", Value::hex32(entry_addr)));
             }
             TagVariant::Flags { console_flags } => {
-                write!(f, "    Console    : 0x{:.08x}\n", console_flags)?;
+                let flags = ConsoleFlags::from_bits_truncate(console_flags);
+                fields.push(Field::new("console_flags",
+                                       "Console",
+                                       Value::Flags {
+                                           bits: u64::from(console_flags),
+                                           digits: 8,
+                                           names: flags.names(),
+                                       }));
             }
             TagVariant::Framebuffer {
                 width,
                 height,
                 depth,
             } => {
-                write!(f, "    Width      : {}\n", width)?;
-                write!(f, "    Height     : {}\n", height)?;
-                write!(f, "    Depth      : {}\n", depth)?;
+                let preference = |x| match x {
+                    GRAPHICS_NO_PREFERENCE => Value::Text("no preference".into()),
+                    x => Value::Integer(u64::from(x)),
+                };
+                fields.push(Field::new("width", "Width", preference(width)));
+                fields.push(Field::new("height", "Height", preference(height)));
+                fields.push(Field::new("depth", "Depth", preference(depth)));
             }
             TagVariant::Relocatable {
                 min_addr,
@@ -295,33 +665,125 @@ impl fmt::Display for Tag {
                 align,
                 preference,
             } => {
-                write!(f, "    Min Addr   : 0x{:.08x}\n", min_addr)?;
-                write!(f, "    Max Addr   : 0x{:.08x}\n", max_addr)?;
-                write!(f, "    Align      : 0x{:.08x}\n", align)?;
-                write!(f, "    Preference : {}\n", match preference {
-                    0 => "none",
-                    1 => "minimum",
-                    2 => "maximum",
-                    _ => "unknown",
-                })?;
+                fields.push(Field::new("min_addr", "Min Addr", Value::hex32(min_addr)));
+                fields.push(Field::new("max_addr", "Max Addr", Value::hex32(max_addr)));
+                fields.push(Field::new("align", "Align", Value::hex32(align)));
+                fields.push(Field::new("preference",
+                                       "Preference",
+                                       Value::Enum {
+                                           value: u64::from(preference),
+                                           name: match preference {
+                                               0 => "none",
+                                               1 => "minimum",
+                                               2 => "maximum",
+                                               _ => "unknown",
+                                           },
+                                       }));
+            }
+            TagVariant::Custom { fields: ref custom, .. } => fields.extend(custom.iter().cloned()),
+            TagVariant::Unknown { ref data } => {
+                fields.push(Field::new("data",
+                                       "Data",
+                                       Value::Bytes {
+                                           offset: self.offset + size_of::<u32>() * 2,
+                                           data: data.to_vec(),
+                                       }));
             }
             _ => {}
         }
-        Ok(())
+
+        fields
     }
 }
 
-pub fn register(descs: &mut Vec<super::Descriptor>) {
+impl fmt::Display for Header {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        super::fields::write_text(self, f)
+    }
+}
+
+impl fmt::Display for TagVariant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let s = match *self {
+            TagVariant::Custom { name, .. } => name,
+            TagVariant::InformationRequest { .. } => "Information Request",
+            TagVariant::Address { .. } => "Address",
+            TagVariant::Entry { .. } => "Entry",
+            TagVariant::EfiI386Entry { .. } => "EFI i386 Entry",
+            TagVariant::EfiAmd64Entry { .. } => "EFI amd64 Entry",
+            TagVariant::Flags { .. } => "Flags",
+            TagVariant::Framebuffer { .. } => "Framebuffer",
+            TagVariant::EfiBootServices => "EFI Boot Services",
+            TagVariant::ModuleAlignment => "Module Alignment",
+            TagVariant::Relocatable { .. } => "Relocatable",
+            TagVariant::Unknown { .. } => "Unknown",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl Flags {
+    /// The names of the flags that are set
+    pub fn names(&self) -> Vec<&'static str> {
+        let mut flags = vec![];
+
+        if self.contains(Flags::OPTIONAL) {
+            flags.push("optional");
+        } else {
+            flags.push("required");
+        }
+
+        flags
+    }
+}
+
+impl ConsoleFlags {
+    /// The names of the flags that are set
+    pub fn names(&self) -> Vec<&'static str> {
+        let mut flags = vec![];
+
+        if self.contains(ConsoleFlags::CONSOLE_REQUIRED) {
+            flags.push("console-required");
+        }
+
+        if self.contains(ConsoleFlags::EGA_TEXT_SUPPORTED) {
+            flags.push("ega-text-supported");
+        }
+
+        flags
+    }
+}
+
+impl fmt::Display for Flags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "[{}]", self.names().join(", "))
+    }
+}
+
+pub fn register(descs: &mut Vec<super::Descriptor>, decoders: &'static [TagDecoder]) {
     descs.push(super::Descriptor {
                    name: "multiboot2",
-                   max_range: 32768,
-                   parser: Header::parse,
+                   // Look past the search limit so that misplaced headers can be reported
+                   max_range: 65536,
+                   parser: Box::new(move |buf| {
+                       Header::parse_with(buf, decoders)
+                           .map(|h| Box::new(h) as Box<dyn super::BootInfo>)
+                   }),
+                   scanner: Box::new(move |buf| {
+                       Header::parse_all_with(buf, decoders).map(|headers| {
+                           headers
+                               .into_iter()
+                               .map(|h| Box::new(h) as Box<dyn super::BootInfo>)
+                               .collect()
+                       })
+                   }),
                })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use parsers::BootInfo;
     use std::io;
     use utils;
     const MULTIBOOT2: &[u8; 40000] = include_bytes!("../../test-data/multiboot2");
@@ -334,6 +796,358 @@ mod tests {
         Header::parse(bytes).unwrap();
     }
 
+    #[test]
+    fn offsets_of_multiboot2() {
+        let cursor = io::Cursor::new(MULTIBOOT2.as_ref());
+        let bytes = utils::header_bytes(cursor, 32768).unwrap();
+        let header = Header::parse(bytes).unwrap();
+        assert_eq!(header.offset(), 0x98);
+        assert_eq!(header.tags()[0].offset(), header.offset() + 16);
+        for pair in header.tags().windows(2) {
+            let size = (pair[0].size() as usize + 7) & !7;
+            assert_eq!(pair[1].offset(), pair[0].offset() + size);
+        }
+    }
+
+    #[test]
+    fn json_of_multiboot2() {
+        let cursor = io::Cursor::new(MULTIBOOT2.as_ref());
+        let bytes = utils::header_bytes(cursor, 32768).unwrap();
+        let json = super::super::fields::to_json(&Header::parse(bytes).unwrap());
+        assert_eq!(json["format"], "multiboot2");
+        assert_eq!(json["fields"]["magic"], u64::from(MAGIC));
+        assert_eq!(json["fields"]["architecture"], json!({"value": 0, "name": "i386"}));
+        let tag = &json["fields"]["tags"][0];
+        assert_eq!(tag["title"], "Tag: Information Request (1)");
+        assert_eq!(tag["fields"]["mbi_tag_types"],
+                   json!([{"value": 4, "name": "basic meminfo"}, {"value": 6, "name": "mmap"}]));
+    }
+
+    #[test]
+    fn decode_console_and_framebuffer() {
+        let cursor = io::Cursor::new(MULTIBOOT2.as_ref());
+        let bytes = utils::header_bytes(cursor, 32768).unwrap();
+        let header = Header::parse(bytes).unwrap();
+        let tag_fields = |typ| {
+            let tag = header.tags().iter().find(|tag| tag.typ() == typ).unwrap();
+            tag.fields().into_iter().skip(3).map(|f| f.value.to_string()).collect::<Vec<_>>()
+        };
+        assert_eq!(tag_fields(TAG_FLAGS), ["[ega-text-supported] (0x00000002)"]);
+        assert_eq!(tag_fields(TAG_FRAMEBUFFER),
+                   ["no preference", "no preference", "no preference"]);
+    }
+
+    fn information_request(flags: u16, types: &[u32]) -> Vec<u8> {
+        let mut tags = tag(TAG_INFORMATION_REQUEST, flags, 8 + 4 * types.len() as u32);
+        for typ in types {
+            tags.extend_from_slice(&typ.to_le_bytes());
+        }
+        if types.len() % 2 == 1 {
+            tags.extend_from_slice(&[0; 4]);
+        }
+        tags.extend(tag(TAG_ENDING, 0, 8));
+        tags
+    }
+
+    #[test]
+    fn warn_about_unknown_required_information() {
+        let tags = information_request(0, &[1, 21, 99]);
+        let header = Header::parse(header_with_tags(&tags, 16 + tags.len() as u32).into())
+            .unwrap();
+        let fields = header.tags()[0].fields();
+        assert_eq!(fields[3].value.to_string(),
+                   "[boot command line (1), load base address (21), unknown (99)]");
+        let warnings = header.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("unknown type 99"));
+
+        let tags = information_request(Flags::OPTIONAL.bits(), &[99]);
+        let header = Header::parse(header_with_tags(&tags, 16 + tags.len() as u32).into())
+            .unwrap();
+        assert!(header.warnings().is_empty());
+    }
+
+    #[test]
+    fn diagnose_header_length_past_end() {
+        let header_length: u32 = 0x1000;
+        let mut data = vec![0u8; 64];
+        data[8..12].copy_from_slice(&MAGIC.to_le_bytes());
+        data[16..20].copy_from_slice(&header_length.to_le_bytes());
+        let checksum = 0u32.wrapping_sub(MAGIC).wrapping_sub(header_length);
+        data[20..24].copy_from_slice(&checksum.to_le_bytes());
+        match Header::parse(data.into()) {
+            Err(Rejection::Invalid { offset, reason }) => {
+                assert_eq!(offset, 8);
+                assert!(reason.starts_with("header length"));
+            }
+            other => panic!("unexpected outcome {:?}", other),
+        }
+    }
+
+    /// Build a header at offset 0 around raw tag bytes, claiming `header_length` bytes
+    fn header_with_tags(tags: &[u8], header_length: u32) -> Vec<u8> {
+        let checksum = 0u32.wrapping_sub(MAGIC).wrapping_sub(header_length);
+        let mut data = vec![];
+        data.extend_from_slice(&MAGIC.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&header_length.to_le_bytes());
+        data.extend_from_slice(&checksum.to_le_bytes());
+        data.extend_from_slice(tags);
+        data
+    }
+
+    fn tag(typ: u16, flags: u16, size: u32) -> Vec<u8> {
+        let mut data = vec![];
+        data.extend_from_slice(&typ.to_le_bytes());
+        data.extend_from_slice(&flags.to_le_bytes());
+        data.extend_from_slice(&size.to_le_bytes());
+        data
+    }
+
+    fn assert_invalid(data: Vec<u8>, expected_offset: usize) {
+        match Header::parse(data.into()) {
+            Err(Rejection::Invalid { offset, .. }) => assert_eq!(offset, expected_offset),
+            other => panic!("unexpected outcome {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reject_tag_size_past_end_of_buffer() {
+        let mut tags = tag(TAG_ENTRY_ADDRESS, 0, 0x1000);
+        tags.extend_from_slice(&[0; 4]);
+        let length = 16 + tags.len() as u32;
+        assert_invalid(header_with_tags(&tags, length), 16);
+    }
+
+    #[test]
+    fn reject_tag_size_below_tag_header() {
+        let mut tags = tag(TAG_MODULE_ALIGNMENT, 0, 4);
+        tags.extend(tag(TAG_ENDING, 0, 8));
+        let length = 16 + tags.len() as u32;
+        assert_invalid(header_with_tags(&tags, length), 16);
+    }
+
+    #[test]
+    fn reject_tag_too_small_for_contents() {
+        let mut tags = tag(TAG_ADDRESS, 0, 8);
+        tags.extend(tag(TAG_ENDING, 0, 8));
+        let length = 16 + tags.len() as u32;
+        assert_invalid(header_with_tags(&tags, length), 16);
+    }
+
+    #[test]
+    fn reject_missing_ending_tag() {
+        let tags = tag(TAG_MODULE_ALIGNMENT, 0, 8);
+        assert_invalid(header_with_tags(&tags, 24), 24);
+    }
+
+    #[test]
+    fn reject_header_length_too_small() {
+        let tags = tag(TAG_ENDING, 0, 8);
+        assert_invalid(header_with_tags(&tags, 4), 0);
+    }
+
+    #[test]
+    fn parse_minimal_header() {
+        let tags = tag(TAG_ENDING, 0, 8);
+        let header = Header::parse(header_with_tags(&tags, 24).into()).unwrap();
+        assert!(header.tags().is_empty());
+    }
+
+    #[test]
+    fn parse_misaligned_header() {
+        let mut data = vec![0u8; 4];
+        data.extend(header_with_tags(&tag(TAG_ENDING, 0, 8), 24));
+        let header = Header::parse(data.into()).unwrap();
+        assert_eq!(header.offset(), 4);
+        assert!(header.misplaced());
+        assert_eq!(header.warnings().len(), 1);
+    }
+
+    #[test]
+    fn prefer_correctly_placed_header() {
+        let mut data = vec![0u8; 4];
+        data.extend(header_with_tags(&tag(TAG_ENDING, 0, 8), 24));
+        data.extend(vec![0u8; 4]);
+        data.extend(header_with_tags(&tag(TAG_ENDING, 0, 8), 24));
+        let header = Header::parse(data.into()).unwrap();
+        assert_eq!(header.offset(), 32);
+        assert!(!header.misplaced());
+        assert!(header.warnings().is_empty());
+    }
+
+    #[test]
+    fn parse_all_candidates() {
+        let mut data = vec![0u8; 4];
+        data.extend(header_with_tags(&tag(TAG_ENDING, 0, 8), 24));
+        data.extend(vec![0u8; 4]);
+        data.extend(header_with_tags(&tag(TAG_ENDING, 0, 8), 24));
+        data.extend(header_with_tags(&tag(TAG_ENDING, 0, 8), 24));
+        let headers = Header::parse_all(data.into()).unwrap();
+        let offsets: Vec<usize> = headers.iter().map(|h| h.offset()).collect();
+        assert_eq!(offsets, vec![4, 32, 56]);
+        let selected: Vec<bool> = headers.iter().map(|h| h.selected()).collect();
+        assert_eq!(selected, vec![false, true, false]);
+    }
+
+    #[test]
+    fn parse_header_past_search_limit() {
+        let mut data = vec![0u8; SEARCH_LIMIT];
+        data.extend(header_with_tags(&tag(TAG_ENDING, 0, 8), 24));
+        let header = Header::parse(data.into()).unwrap();
+        assert!(header.misplaced());
+    }
+
+    fn address_tags(addrs: [u32; 4], entry: u32) -> Vec<u8> {
+        let mut tags = tag(TAG_ADDRESS, 0, 24);
+        for addr in &addrs {
+            tags.extend_from_slice(&addr.to_le_bytes());
+        }
+        tags.extend(tag(TAG_ENTRY_ADDRESS, 0, 12));
+        tags.extend_from_slice(&entry.to_le_bytes());
+        tags.extend_from_slice(&[0; 4]);
+        tags.extend(tag(TAG_ENDING, 0, 8));
+        tags
+    }
+
+    #[test]
+    fn cross_check_address_tag() {
+        let tags = address_tags([0x0010_0000, 0x0010_0000, 0x0010_1000, 0x0010_2000], 0x0010_0800);
+        let header = Header::parse(header_with_tags(&tags, 16 + tags.len() as u32).into())
+            .unwrap();
+        assert_eq!(header.kludge().unwrap().load_offset(), Some(0));
+        assert!(header.warnings().is_empty());
+        let json = super::super::fields::to_json(&header);
+        assert_eq!(json["fields"]["tags"][0]["fields"]["load_offset"], 0);
+
+        let tags = address_tags([0x0010_0000, 0x0010_0000, 0x0010_1000, 0x0010_2000], 0x0020_0000);
+        let header = Header::parse(header_with_tags(&tags, 16 + tags.len() as u32).into())
+            .unwrap();
+        let warnings = header.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("entry_addr"));
+    }
+
+    #[test]
+    fn load_address_tag_from_start_of_file() {
+        let tags = address_tags([0x0010_0040, LOAD_FROM_START, 0, 0], 0x0010_0100);
+        let mut data = vec![0u8; 0x40];
+        data.extend(header_with_tags(&tags, 16 + tags.len() as u32));
+        let kludge = Header::parse(data.into()).unwrap().kludge().unwrap();
+        assert_eq!(kludge.load_addr, 0x0010_0000);
+        assert_eq!(kludge.load_offset(), Some(0));
+        assert!(kludge.violations().is_empty());
+    }
+
+    /// A header with a tag of type 0x8000 whose payload is `payload`
+    fn vendor_tag(payload: &[u8]) -> Vec<u8> {
+        let mut tags = tag(0x8000, Flags::OPTIONAL.bits(), 8 + payload.len() as u32);
+        tags.extend_from_slice(payload);
+        tags.resize((tags.len() + 7) & !7, 0);
+        tags.extend(tag(TAG_ENDING, 0, 8));
+        header_with_tags(&tags, 16 + tags.len() as u32)
+    }
+
+    #[test]
+    fn dump_unknown_tag() {
+        let header = Header::parse(vendor_tag(b"vendor blob\x00\x01\x02\x03\x04\xff").into())
+            .unwrap();
+        let text = header.to_string();
+        assert!(text.contains("Tag: Unknown (32768)"), "{}", text);
+        assert!(text.contains("Data   : 17 bytes\n"), "{}", text);
+        assert!(text.contains("0x00000018: 76 65 6e 64 6f 72 20 62 6c 6f 62 00 01 02 03 04  \
+                               |vendor blob.....|\n"),
+                "{}",
+                text);
+        assert!(text.contains("0x00000028: ff                                               |.|\n"),
+                "{}",
+                text);
+        let json = super::super::fields::to_json(&header);
+        assert_eq!(json["fields"]["tags"][0]["fields"]["data"],
+                   "76656e646f7220626c6f620001020304ff");
+    }
+
+    fn decode_version(data: &[u8]) -> Result<Vec<Field>, String> {
+        if data.len() < 4 {
+            return Err(format!("{} bytes is too short for a version", data.len()));
+        }
+        let version = u32::from(data[0]) | u32::from(data[1]) << 8 | u32::from(data[2]) << 16 |
+                      u32::from(data[3]) << 24;
+        Ok(vec![Field::new("version", "Version", Value::Integer(u64::from(version)))])
+    }
+
+    const DECODERS: &[TagDecoder] = &[TagDecoder {
+                                          typ: 0x8000,
+                                          name: "Vendor Version",
+                                          decode: decode_version,
+                                      },
+                                      TagDecoder {
+                                          typ: TAG_MODULE_ALIGNMENT,
+                                          name: "Not Used",
+                                          decode: decode_version,
+                                      }];
+
+    #[test]
+    fn decode_custom_tag() {
+        let header = Header::parse_with(vendor_tag(&[7, 0, 0, 0]).into(), DECODERS).unwrap();
+        let json = super::super::fields::to_json(&header);
+        let vendor = &json["fields"]["tags"][0];
+        assert_eq!(vendor["title"], "Tag: Vendor Version (32768)");
+        assert_eq!(vendor["fields"]["version"], 7);
+
+        // Decoders do not replace the ones for the types that the specification defines
+        let mut tags = tag(TAG_MODULE_ALIGNMENT, 0, 8);
+        tags.extend(tag(TAG_ENDING, 0, 8));
+        let header = Header::parse_with(header_with_tags(&tags, 32).into(), DECODERS).unwrap();
+        assert_eq!(header.tags()[0].variant().to_string(), "Module Alignment");
+    }
+
+    #[test]
+    fn decode_custom_tag_through_descriptor() {
+        let descs = ::parsers::register_with(DECODERS);
+        let desc = descs.iter().find(|d| d.name == "multiboot2").unwrap();
+        let data = bytes::Bytes::from(vendor_tag(&[7, 0, 0, 0]));
+        let header = desc.parse(data.clone()).unwrap();
+        let json = super::super::fields::to_json(&*header);
+        assert_eq!(json["fields"]["tags"][0]["fields"]["version"], 7);
+        let headers = desc.parse_all(data).unwrap();
+        let json = super::super::fields::to_json(&*headers[0]);
+        assert_eq!(json["fields"]["tags"][0]["fields"]["version"], 7);
+
+        // Without the decoder, the tag is only dumped
+        let descs = ::parsers::register();
+        let desc = descs.iter().find(|d| d.name == "multiboot2").unwrap();
+        let header = desc.parse(vendor_tag(&[7, 0, 0, 0]).into()).unwrap();
+        let json = super::super::fields::to_json(&*header);
+        assert!(json["fields"]["tags"][0]["fields"]["version"].is_null());
+    }
+
+    #[test]
+    fn keep_tag_that_decoder_rejects() {
+        let mut data = vendor_tag(&[7]);
+        // Follow the vendor tag with a standard one, which must still be decoded
+        let ending = data.len() - 8;
+        data.truncate(ending);
+        data.extend(tag(TAG_MODULE_ALIGNMENT, 0, 8));
+        data.extend(tag(TAG_ENDING, 0, 8));
+        let length = data.len() as u32;
+        let checksum = 0u32.wrapping_sub(MAGIC).wrapping_sub(length);
+        data[8..12].copy_from_slice(&length.to_le_bytes());
+        data[12..16].copy_from_slice(&checksum.to_le_bytes());
+
+        let header = Header::parse_with(data.into(), DECODERS).unwrap();
+        assert_eq!(header.tags().len(), 2);
+        match *header.tags()[0].variant() {
+            TagVariant::Unknown { ref data } => assert_eq!(&data[..], &[7]),
+            ref other => panic!("unexpected variant {:?}", other),
+        }
+        assert!(header.tags()[0].fields().iter().any(|f| f.name == "data"));
+        assert_eq!(header.tags()[1].variant().to_string(), "Module Alignment");
+        assert_eq!(header.warnings(),
+                   ["Vendor Version tag at 0x10 could not be decoded: 1 bytes is too short for \
+                     a version"]);
+    }
+
     #[test]
     #[should_panic]
     fn parse_invalid_linuxboot() {
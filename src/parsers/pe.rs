@@ -0,0 +1,724 @@
+//! PE/COFF images, such as EFI applications and the EFI stub that a bzImage doubles as.
+//!
+//! Standalone images are registered as the `efi` format, and other parsers use `Pe` to describe
+//! the PE headers of the images they recognize.
+
+use super::linux;
+use super::{Field, Group, Rejection, Value};
+use bytes;
+use std::fmt;
+
+/// The signature at the start of the MS-DOS stub
+pub const MZ_MAGIC: &[u8] = b"MZ";
+
+/// The signature at the start of the PE header
+pub const PE_MAGIC: &[u8] = b"PE\0\0";
+
+/// The offset of the PE header's offset within the MS-DOS stub
+pub(crate) const PE_OFFSET_OFFSET: usize = 0x3c;
+
+/// The size of the signature and COFF file header that precede the optional header
+pub(crate) const COFF_HEADER_SIZE: usize = 24;
+
+/// The size of an entry in the section table
+pub(crate) const SECTION_HEADER_SIZE: usize = 40;
+
+const PE32_MAGIC: u16 = 0x10b;
+pub(crate) const PE32_PLUS_MAGIC: u16 = 0x20b;
+
+/// The image machine types that EFI firmware runs
+pub const MACHINE_I386: u16 = 0x14c;
+pub const MACHINE_AMD64: u16 = 0x8664;
+
+/// The number of data directories that the PE format defines
+const MAX_DATA_DIRECTORIES: usize = 16;
+
+/// The DLL characteristic that marks the image as compatible with non-executable data
+pub const DLL_NX_COMPAT: u16 = 0x100;
+
+const SCN_MEM_EXECUTE: u32 = 0x2000_0000;
+const SCN_MEM_WRITE: u32 = 0x8000_0000;
+
+/// The page size that UEFI applies memory protections with, a power of two
+const EFI_PAGE_SIZE: u32 = 0x1000;
+
+/// Little-endian reads that are bounds-checked against the image
+fn bytes(data: &[u8], offset: usize, len: usize) -> Result<&[u8], Rejection> {
+    offset.checked_add(len)
+        .and_then(|end| data.get(offset..end))
+        .ok_or_else(|| {
+                        Rejection::invalid(offset,
+                                           format!("PE image is truncated, needed {} bytes", len))
+                    })
+}
+
+fn uint(data: &[u8], offset: usize, len: usize) -> Result<u64, Rejection> {
+    Ok(bytes(data, offset, len)?
+           .iter()
+           .rev()
+           .fold(0, |value, byte| value << 8 | u64::from(*byte)))
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Result<u16, Rejection> {
+    uint(data, offset, 2).map(|value| value as u16)
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Result<u32, Rejection> {
+    uint(data, offset, 4).map(|value| value as u32)
+}
+
+/// An entry in the section table
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Section {
+    offset: usize,
+    name: String,
+    virtual_size: u32,
+    virtual_address: u32,
+    raw_size: u32,
+    raw_offset: u32,
+    characteristics: u32,
+}
+
+/// An entry in the data directory of the optional header, locating a table such as the base
+/// relocations
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DataDirectory {
+    index: usize,
+    rva: u32,
+    size: u32,
+}
+
+/// The parts of a PE/COFF image that matter for booting it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pe {
+    offset: usize,
+    machine: u16,
+    characteristics: u16,
+    pe32_plus: bool,
+    entry: u32,
+    image_base: u64,
+    section_alignment: u32,
+    file_alignment: u32,
+    size_of_image: u32,
+    size_of_headers: u32,
+    subsystem: u16,
+    dll_characteristics: u16,
+    data_directories: Vec<DataDirectory>,
+    sections: Vec<Section>,
+}
+
+impl Pe {
+    /// Parse the PE/COFF image at the start of `data`
+    pub fn parse(data: &[u8]) -> Result<Pe, Rejection> {
+        if !data.starts_with(MZ_MAGIC) {
+            return Err(Rejection::NotPresent);
+        }
+        let offset = u32_at(data, PE_OFFSET_OFFSET)? as usize;
+        if bytes(data, offset, PE_MAGIC.len()).ok() != Some(PE_MAGIC) {
+            return Err(Rejection::invalid(PE_OFFSET_OFFSET,
+                                          format!("no PE signature at 0x{:x}", offset)));
+        }
+
+        let coff = offset + PE_MAGIC.len();
+        let machine = u16_at(data, coff)?;
+        let nsections = u16_at(data, coff + 2)?;
+        let optional_size = u16_at(data, coff + 16)?;
+        let characteristics = u16_at(data, coff + 18)?;
+
+        let optional = offset + COFF_HEADER_SIZE;
+        let pe32_plus = match u16_at(data, optional)? {
+            PE32_MAGIC => false,
+            PE32_PLUS_MAGIC => true,
+            other => {
+                return Err(Rejection::invalid(optional,
+                                              format!("unknown optional header magic 0x{:x}",
+                                                      other)))
+            }
+        };
+        let entry = u32_at(data, optional + 16)?;
+        let image_base = if pe32_plus {
+            uint(data, optional + 24, 8)?
+        } else {
+            u64::from(u32_at(data, optional + 28)?)
+        };
+
+        // The directories take up the rest of the optional header
+        let (count_offset, directories_offset) = if pe32_plus { (108, 112) } else { (92, 96) };
+        let mut data_directories = vec![];
+        if usize::from(optional_size) >= directories_offset {
+            let count = (u32_at(data, optional + count_offset)? as usize)
+                .min(MAX_DATA_DIRECTORIES)
+                .min((usize::from(optional_size) - directories_offset) / 8);
+            for index in 0..count {
+                let offset = optional + directories_offset + index * 8;
+                data_directories.push(DataDirectory {
+                                          index,
+                                          rva: u32_at(data, offset)?,
+                                          size: u32_at(data, offset + 4)?,
+                                      });
+            }
+        }
+
+        let mut sections = vec![];
+        let table = optional + usize::from(optional_size);
+        for index in 0..usize::from(nsections) {
+            sections.push(Section::parse(data, table + index * SECTION_HEADER_SIZE)?);
+        }
+
+        Ok(Pe {
+               offset,
+               machine,
+               characteristics,
+               pe32_plus,
+               entry,
+               image_base,
+               section_alignment: u32_at(data, optional + 32)?,
+               file_alignment: u32_at(data, optional + 36)?,
+               size_of_image: u32_at(data, optional + 56)?,
+               size_of_headers: u32_at(data, optional + 60)?,
+               subsystem: u16_at(data, optional + 68)?,
+               dll_characteristics: u16_at(data, optional + 70)?,
+               data_directories,
+               sections,
+           })
+    }
+
+    /// The offset of the PE signature within the image
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The architecture the image was built for
+    pub fn machine(&self) -> u16 {
+        self.machine
+    }
+
+    /// The COFF characteristics, such as whether the image is executable
+    pub fn characteristics(&self) -> u16 {
+        self.characteristics
+    }
+
+    /// Whether the image has a PE32+ optional header, with 64-bit addresses
+    pub fn pe32_plus(&self) -> bool {
+        self.pe32_plus
+    }
+
+    /// The address that execution starts at, relative to the image base
+    pub fn entry(&self) -> u32 {
+        self.entry
+    }
+
+    /// The preferred address to load the image at
+    pub fn image_base(&self) -> u64 {
+        self.image_base
+    }
+
+    /// The alignment of the sections once loaded
+    pub fn section_alignment(&self) -> u32 {
+        self.section_alignment
+    }
+
+    /// The alignment of the section contents within the image
+    pub fn file_alignment(&self) -> u32 {
+        self.file_alignment
+    }
+
+    /// The size of the image once loaded
+    pub fn size_of_image(&self) -> u32 {
+        self.size_of_image
+    }
+
+    /// The size of the headers, including the section table
+    pub fn size_of_headers(&self) -> u32 {
+        self.size_of_headers
+    }
+
+    /// The environment the image runs in, such as an EFI application
+    pub fn subsystem(&self) -> u16 {
+        self.subsystem
+    }
+
+    /// The DLL characteristics, such as whether the image is NX-compatible
+    pub fn dll_characteristics(&self) -> u16 {
+        self.dll_characteristics
+    }
+
+    /// Whether the image can run with its data mapped non-executable
+    pub fn nx_compat(&self) -> bool {
+        self.dll_characteristics & DLL_NX_COMPAT != 0
+    }
+
+    /// The entries of the data directory, including empty ones
+    pub fn data_directories(&self) -> &[DataDirectory] {
+        &self.data_directories
+    }
+
+    /// The sections described by the section table
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    /// The first section with the given name
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+
+    /// The section that an address relative to the image base is loaded into
+    pub fn section_at(&self, rva: u32) -> Option<&Section> {
+        self.sections.iter().find(|section| section.contains(rva))
+    }
+
+    /// The offset within the image of an address relative to the image base
+    pub fn rva_to_offset(&self, rva: u32) -> Option<usize> {
+        self.section_at(rva)
+            .filter(|section| rva - section.virtual_address < section.raw_size)
+            .map(|section| section.raw_offset as usize + (rva - section.virtual_address) as usize)
+    }
+
+    fn address(&self, value: u64) -> Value {
+        Value::Hex {
+            value,
+            digits: if self.pe32_plus { 16 } else { 8 },
+        }
+    }
+
+    /// The fields of the headers, leaving out the section table
+    pub fn header_fields(&self) -> Vec<Field> {
+        vec![Field::new("pe_offset", "PE Offset", Value::offset(self.offset)),
+             Field::new("pe_format",
+                        "PE Format",
+                        Value::Enum {
+                            value: u64::from(if self.pe32_plus {
+                                                 PE32_PLUS_MAGIC
+                                             } else {
+                                                 PE32_MAGIC
+                                             }),
+                            name: if self.pe32_plus { "PE32+" } else { "PE32" },
+                        }),
+             Field::new("machine",
+                        "Machine",
+                        Value::Enum {
+                            value: u64::from(self.machine),
+                            name: machine_name(self.machine),
+                        }),
+             Field::new("subsystem",
+                        "Subsystem",
+                        Value::Enum {
+                            value: u64::from(self.subsystem),
+                            name: subsystem_name(self.subsystem),
+                        }),
+             Field::new("entry", "Entry", Value::hex32(self.entry)),
+             Field::new("image_base", "Image Base", self.address(self.image_base)),
+             Field::new("size_of_image", "Image Size", Value::Size(u64::from(self.size_of_image)))]
+    }
+
+    /// The section table, one group per section
+    pub fn section_groups(&self) -> Value {
+        Value::Tags(self.sections
+                        .iter()
+                        .map(|section| {
+                                 Group {
+                                     title: format!("Section: {}", section.name),
+                                     fields: section.fields(),
+                                 }
+                             })
+                        .collect())
+    }
+}
+
+impl super::BootInfo for Pe {
+    fn format(&self) -> &'static str {
+        "efi"
+    }
+
+    fn title(&self) -> &'static str {
+        "PE/COFF Image"
+    }
+
+    fn fields(&self) -> Vec<Field> {
+        let mut fields = vec![Field::new("offset", "Offset", Value::offset(0))];
+        fields.extend(self.header_fields());
+        let names = [(0x0020, "high-entropy-va"),
+                     (0x0040, "dynamic-base"),
+                     (0x0080, "force-integrity"),
+                     (DLL_NX_COMPAT, "nx-compat"),
+                     (0x0200, "no-isolation"),
+                     (0x0400, "no-seh"),
+                     (0x0800, "no-bind"),
+                     (0x1000, "appcontainer"),
+                     (0x2000, "wdm-driver"),
+                     (0x4000, "guard-cf"),
+                     (0x8000, "terminal-server-aware")]
+            .iter()
+            .filter(|&&(bit, _)| self.dll_characteristics & bit != 0)
+            .map(|&(_, name)| name)
+            .collect();
+        fields.push(Field::new("dll_characteristics",
+                               "DLL Characteristics",
+                               Value::Flags {
+                                   bits: u64::from(self.dll_characteristics),
+                                   digits: 4,
+                                   names,
+                               }));
+        fields.push(Field::new("nx_compat", "NX Compatible", Value::Bool(self.nx_compat())));
+        // Most of the directories are empty, so leave those out
+        fields.push(Field::new("data_directories",
+                               "Data Directories",
+                               Value::Tags(self.data_directories
+                                               .iter()
+                                               .filter(|dir| dir.rva != 0 || dir.size != 0)
+                                               .map(|dir| {
+                                                        Group {
+                                                            title: format!("Data Directory: {}",
+                                                                           dir.name()),
+                                                            fields: dir.fields(),
+                                                        }
+                                                    })
+                                               .collect())));
+        fields.push(Field::new("sections", "Sections", self.section_groups()));
+        fields
+    }
+
+    fn warnings(&self) -> Vec<String> {
+        let mut warnings = vec![];
+        // Firmware can only enforce NX for images laid out in whole pages without W+X sections
+        if self.nx_compat() {
+            if self.section_alignment & (EFI_PAGE_SIZE - 1) != 0 {
+                warnings.push(format!("NX-compatible image has a section alignment of 0x{:x}, \
+                                       not a multiple of the 4 KiB page size",
+                                      self.section_alignment));
+            }
+            for section in &self.sections {
+                if section.characteristics & (SCN_MEM_EXECUTE | SCN_MEM_WRITE) ==
+                   SCN_MEM_EXECUTE | SCN_MEM_WRITE {
+                    warnings.push(format!("NX-compatible image has a section {} that is both \
+                                           writable and executable",
+                                          section.name));
+                }
+            }
+        }
+        warnings
+    }
+}
+
+impl fmt::Display for Pe {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        super::fields::write_text(self, f)
+    }
+}
+
+impl DataDirectory {
+    /// The position of the entry in the data directory, which determines the kind of table
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The address of the table, relative to the image base
+    pub fn rva(&self) -> u32 {
+        self.rva
+    }
+
+    /// The size of the table
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// The kind of table the entry locates
+    pub fn name(&self) -> &'static str {
+        match self.index {
+            0 => "export",
+            1 => "import",
+            2 => "resource",
+            3 => "exception",
+            4 => "certificate",
+            5 => "base relocation",
+            6 => "debug",
+            7 => "architecture",
+            8 => "global pointer",
+            9 => "TLS",
+            10 => "load config",
+            11 => "bound import",
+            12 => "import address",
+            13 => "delay import",
+            14 => "CLR runtime",
+            _ => "reserved",
+        }
+    }
+
+    fn fields(&self) -> Vec<Field> {
+        // The certificate table is the one entry that holds a file offset instead of an address
+        let label = if self.index == 4 {
+            "File Offset"
+        } else {
+            "Virt Addr"
+        };
+        vec![Field::new("rva", label, Value::hex32(self.rva)),
+             Field::new("size", "Size", Value::Size(u64::from(self.size)))]
+    }
+}
+
+impl Section {
+    fn parse(data: &[u8], offset: usize) -> Result<Section, Rejection> {
+        let name = bytes(data, offset, 8)?;
+        let name = String::from_utf8_lossy(name.split(|&b| b == 0).next().unwrap_or(name));
+        Ok(Section {
+               offset,
+               name: name.into_owned(),
+               virtual_size: u32_at(data, offset + 8)?,
+               virtual_address: u32_at(data, offset + 12)?,
+               raw_size: u32_at(data, offset + 16)?,
+               raw_offset: u32_at(data, offset + 20)?,
+               characteristics: u32_at(data, offset + 36)?,
+           })
+    }
+
+    /// The offset of the section header within the image
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The name of the section, such as `.text`
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The size of the section once loaded
+    pub fn virtual_size(&self) -> u32 {
+        self.virtual_size
+    }
+
+    /// The address the section is loaded at, relative to the image base
+    pub fn virtual_address(&self) -> u32 {
+        self.virtual_address
+    }
+
+    /// The size of the section contents within the image
+    pub fn raw_size(&self) -> u32 {
+        self.raw_size
+    }
+
+    /// The offset of the section contents within the image
+    pub fn raw_offset(&self) -> u32 {
+        self.raw_offset
+    }
+
+    /// The kind of contents and the permissions of the section
+    pub fn characteristics(&self) -> u32 {
+        self.characteristics
+    }
+
+    /// Whether an address relative to the image base is loaded into the section
+    pub fn contains(&self, rva: u32) -> bool {
+        let size = self.virtual_size.max(self.raw_size);
+        rva >= self.virtual_address && rva - self.virtual_address < size
+    }
+
+    /// The contents of the section, if they lie within `data`
+    pub fn data<'a>(&self, data: &'a [u8]) -> Option<&'a [u8]> {
+        // The file data is padded out to the file alignment, past the size that gets loaded
+        let start = self.raw_offset as usize;
+        let len = if self.virtual_size == 0 {
+            self.raw_size
+        } else {
+            self.raw_size.min(self.virtual_size)
+        } as usize;
+        start.checked_add(len).and_then(|end| data.get(start..end))
+    }
+
+    fn fields(&self) -> Vec<Field> {
+        let names = [(0x20, "code"),
+                     (0x40, "initialized-data"),
+                     (0x80, "uninitialized-data"),
+                     (0x0200_0000, "discardable"),
+                     (0x2000_0000, "execute"),
+                     (0x4000_0000, "read"),
+                     (0x8000_0000, "write")]
+            .iter()
+            .filter(|&&(bit, _)| self.characteristics & bit != 0)
+            .map(|&(_, name)| name)
+            .collect();
+        vec![Field::new("offset", "Offset", Value::offset(self.offset)),
+             Field::new("virtual_address", "Virt Addr", Value::hex32(self.virtual_address)),
+             Field::new("virtual_size", "Virt Size", Value::Size(u64::from(self.virtual_size))),
+             Field::new("raw_offset", "File Offset", Value::hex32(self.raw_offset)),
+             Field::new("raw_size", "File Size", Value::Size(u64::from(self.raw_size))),
+             Field::new("characteristics",
+                        "Characteristics",
+                        Value::Flags {
+                            bits: u64::from(self.characteristics),
+                            digits: 8,
+                            names,
+                        })]
+    }
+}
+
+/// The name of a machine type, as EFI names the architecture
+pub fn machine_name(machine: u16) -> &'static str {
+    match machine {
+        MACHINE_I386 => "ia32",
+        MACHINE_AMD64 => "x64",
+        0x1c2 | 0x1c4 => "arm",
+        0xaa64 => "aa64",
+        0xebc => "ebc",
+        0x5032 => "riscv32",
+        0x5064 => "riscv64",
+        0x5128 => "riscv128",
+        0x6232 => "loongarch32",
+        0x6264 => "loongarch64",
+        _ => "unknown",
+    }
+}
+
+fn subsystem_name(subsystem: u16) -> &'static str {
+    match subsystem {
+        1 => "native",
+        2 => "Windows GUI",
+        3 => "Windows console",
+        10 => "EFI application",
+        11 => "EFI boot service driver",
+        12 => "EFI runtime driver",
+        13 => "EFI ROM",
+        _ => "unknown",
+    }
+}
+
+/// Parse a standalone PE/COFF image, leaving Linux kernels to the `linux-efi` format
+fn parse_efi(buf: bytes::Bytes) -> Result<Pe, Rejection> {
+    let pe = Pe::parse(&buf)?;
+    // Even a kernel whose setup header is broken is still a kernel, not a standalone image
+    match linux::Header::parse(buf) {
+        Err(Rejection::NotPresent) => Ok(pe),
+        _ => Err(Rejection::NotPresent),
+    }
+}
+
+pub fn register(descs: &mut Vec<super::Descriptor>) {
+    descs.push(super::Descriptor {
+                   name: "efi",
+                   max_range: 32768,
+                   parser: Box::new(|buf| {
+                       parse_efi(buf).map(|pe| Box::new(pe) as Box<dyn super::BootInfo>)
+                   }),
+                   // There is only ever one PE header, at the offset that the MS-DOS stub gives
+                   scanner: Box::new(|buf| {
+                       parse_efi(buf).map(|pe| vec![Box::new(pe) as Box<dyn super::BootInfo>])
+                   }),
+               })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parsers::builders::pe32_plus;
+    use parsers::BootInfo;
+
+    #[test]
+    fn parse_pe32_plus() {
+        let data = pe32_plus(0x80,
+                             0x1010,
+                             &[(".text", 0x1000, 0x100, 0x400, 0x100),
+                               (".data", 0x2000, 0x80, 0x500, 0x80)]);
+        let pe = Pe::parse(&data).unwrap();
+        assert_eq!(pe.offset(), 0x80);
+        assert_eq!(pe.machine(), MACHINE_AMD64);
+        assert!(pe.pe32_plus());
+        assert_eq!(pe.entry(), 0x1010);
+        assert_eq!(pe.subsystem(), 10);
+        assert_eq!(pe.size_of_image(), 0x2080);
+        assert_eq!(pe.sections().len(), 2);
+        assert_eq!(pe.section_at(0x1010).map(Section::name), Some(".text"));
+        assert_eq!(pe.rva_to_offset(0x2010), Some(0x510));
+        assert_eq!(pe.rva_to_offset(0x3000), None);
+        let names: Vec<_> = pe.fields().iter().map(|f| f.name).collect();
+        assert_eq!(names,
+                   ["offset", "pe_offset", "pe_format", "machine", "subsystem", "entry",
+                    "image_base", "size_of_image", "dll_characteristics", "nx_compat",
+                    "data_directories", "sections"]);
+    }
+
+    #[test]
+    fn decode_data_directories() {
+        let mut data = pe32_plus(0x80, 0x1000, &[(".text", 0x1000, 0x100, 0x400, 0x100)]);
+        let directories = 0x80 + COFF_HEADER_SIZE + 112;
+        data[directories + 5 * 8..directories + 5 * 8 + 8]
+            .copy_from_slice(&[0x00, 0x30, 0, 0, 0x0c, 0, 0, 0]);
+        let pe = Pe::parse(&data).unwrap();
+        assert_eq!(pe.data_directories().len(), 6);
+        let reloc = &pe.data_directories()[5];
+        assert_eq!((reloc.name(), reloc.rva(), reloc.size()), ("base relocation", 0x3000, 12));
+        let fields = pe.fields();
+        let dirs = fields.iter().find(|f| f.name == "data_directories").unwrap();
+        assert_eq!(dirs.value.to_json(),
+                   json!([{"title": "Data Directory: base relocation",
+                           "fields": {"rva": 0x3000, "size": 12}}]));
+    }
+
+    #[test]
+    fn check_nx_compat_layout() {
+        let mut data = pe32_plus(0x80,
+                                 0x1000,
+                                 &[(".text", 0x1000, 0x100, 0x400, 0x100),
+                                   (".data", 0x2000, 0x80, 0x500, 0x80)]);
+        let optional = 0x80 + COFF_HEADER_SIZE;
+        assert!(!Pe::parse(&data).unwrap().nx_compat());
+
+        data[optional + 70..optional + 72].copy_from_slice(&DLL_NX_COMPAT.to_le_bytes());
+        // Make .data writable and executable
+        let data_header = optional + 112 + 6 * 8 + SECTION_HEADER_SIZE;
+        data[data_header + 36..data_header + 40].copy_from_slice(&0xe000_0040u32.to_le_bytes());
+        let pe = Pe::parse(&data).unwrap();
+        assert!(pe.nx_compat());
+        assert_eq!(pe.warnings().len(), 2);
+
+        data[optional + 32..optional + 36].copy_from_slice(&0x1000u32.to_le_bytes());
+        data[data_header + 36..data_header + 40].copy_from_slice(&0xc000_0040u32.to_le_bytes());
+        assert!(Pe::parse(&data).unwrap().warnings().is_empty());
+    }
+
+    #[test]
+    fn efi_leaves_linux_to_linux_efi() {
+        let data = pe32_plus(0x80, 0x1000, &[(".text", 0x1000, 0x100, 0x400, 0x100)]);
+        assert!(parse_efi(bytes::Bytes::from(data)).is_ok());
+        let linux = include_bytes!("../../test-data/linuxboot");
+        assert!(Pe::parse(linux).is_ok());
+        assert_eq!(parse_efi(bytes::Bytes::from(&linux[..])).unwrap_err(),
+                   Rejection::NotPresent);
+    }
+
+    #[test]
+    fn efi_leaves_truncated_linux_to_linux() {
+        // Cut off in the middle of the setup header, but after the PE section table
+        let linux = bytes::Bytes::from(&include_bytes!("../../test-data/linuxboot")[..0x240]);
+        assert!(Pe::parse(&linux).is_ok());
+        match linux::Header::parse(linux.clone()) {
+            Err(Rejection::Invalid { .. }) => {}
+            other => panic!("unexpected outcome {:?}", other),
+        }
+        assert_eq!(parse_efi(linux).unwrap_err(), Rejection::NotPresent);
+    }
+
+    #[test]
+    fn reject_missing_pe_signature() {
+        let mut data = pe32_plus(0x80, 0, &[]);
+        data[0x80] = b'X';
+        match Pe::parse(&data) {
+            Err(Rejection::Invalid { offset, .. }) => assert_eq!(offset, PE_OFFSET_OFFSET),
+            other => panic!("unexpected outcome {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reject_truncated_section_table() {
+        let data = pe32_plus(0x80, 0, &[(".text", 0x1000, 0x100, 0x400, 0x100)]);
+        let len = data.len();
+        match Pe::parse(&data[..len - 8]) {
+            Err(Rejection::Invalid { .. }) => {}
+            other => panic!("unexpected outcome {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reject_non_pe() {
+        assert_eq!(Pe::parse(b"\x7fELF"), Err(Rejection::NotPresent));
+    }
+}
@@ -0,0 +1,198 @@
+//! Unified Kernel Images, EFI executables that bundle a Linux kernel with its initrd, command line
+//! and the os-release of the system it boots.
+//!
+//! The contents of the sections lie well past the header bytes, so a UKI is parsed from the whole
+//! image rather than through a `Descriptor`.
+
+use super::pe::{Pe, Section};
+use super::{Field, Rejection, Value};
+use std::fmt;
+
+/// The section holding the kernel that the stub boots
+pub const LINUX_SECTION: &str = ".linux";
+
+/// Whether the start of an image is the PE header of a UKI
+pub fn is_uki(data: &[u8]) -> bool {
+    Pe::parse(data).ok().is_some_and(|pe| pe.section(LINUX_SECTION).is_some())
+}
+
+/// The contents of a text section, without the trailing newlines and NULs that pad it
+fn section_text(section: Option<&Section>, image: &[u8]) -> Option<String> {
+    section.and_then(|section| section.data(image))
+        .map(|data| String::from_utf8_lossy(data).trim_end_matches(&['\0', '\n'][..]).to_string())
+}
+
+/// A Unified Kernel Image
+#[derive(Debug)]
+pub struct Uki {
+    pe: Pe,
+    cmdline: Option<String>,
+    osrel: Option<String>,
+    uname: Option<String>,
+    truncated: Vec<String>,
+}
+
+impl Uki {
+    /// Parse the UKI that makes up the whole of `image`
+    pub fn parse(image: &[u8]) -> Result<Uki, Rejection> {
+        let pe = Pe::parse(image)?;
+        let linux = pe.section(LINUX_SECTION).ok_or(Rejection::NotPresent)?;
+        if linux.data(image).is_none() {
+            return Err(Rejection::invalid(linux.offset(),
+                                          ".linux section runs past the end of the data"));
+        }
+
+        let truncated = pe.sections()
+            .iter()
+            .filter(|section| section.data(image).is_none() && section.raw_size() != 0)
+            .map(|section| format!("{} section runs past the end of the data", section.name()))
+            .collect();
+
+        Ok(Uki {
+               cmdline: section_text(pe.section(".cmdline"), image),
+               osrel: section_text(pe.section(".osrel"), image),
+               uname: section_text(pe.section(".uname"), image),
+               pe,
+               truncated,
+           })
+    }
+
+    /// The PE/COFF headers of the stub
+    pub fn pe(&self) -> &Pe {
+        &self.pe
+    }
+
+    /// The kernel in the `.linux` section of `image`
+    pub fn linux<'a>(&self, image: &'a [u8]) -> &'a [u8] {
+        self.pe
+            .section(LINUX_SECTION)
+            .and_then(|section| section.data(image))
+            .unwrap_or(&[])
+    }
+
+    /// The offset of the kernel within the image
+    pub fn linux_offset(&self) -> usize {
+        self.pe.section(LINUX_SECTION).map_or(0, |section| section.raw_offset() as usize)
+    }
+
+    /// The kernel command line in the `.cmdline` section
+    pub fn cmdline(&self) -> Option<&str> {
+        self.cmdline.as_deref()
+    }
+
+    /// The os-release of the system the kernel boots, from the `.osrel` section
+    pub fn osrel(&self) -> Option<&str> {
+        self.osrel.as_deref()
+    }
+
+    /// The kernel release, as `uname -r` prints it, from the `.uname` section
+    pub fn uname(&self) -> Option<&str> {
+        self.uname.as_deref()
+    }
+}
+
+impl super::BootInfo for Uki {
+    fn format(&self) -> &'static str {
+        "uki"
+    }
+
+    fn title(&self) -> &'static str {
+        "Unified Kernel Image"
+    }
+
+    fn fields(&self) -> Vec<Field> {
+        let mut fields = vec![Field::new("offset", "Offset", Value::offset(0)),
+                              Field::new("linux_offset",
+                                         "Linux Offset",
+                                         Value::offset(self.linux_offset()))];
+        if let Some(ref uname) = self.uname {
+            fields.push(Field::new("uname", "Kernel Release", Value::Text(uname.clone())));
+        }
+        if let Some(ref cmdline) = self.cmdline {
+            fields.push(Field::new("cmdline", "Command Line", Value::Text(cmdline.clone())));
+        }
+        if let Some(ref osrel) = self.osrel {
+            fields.push(Field::new("osrel",
+                                   "OS Release",
+                                   Value::List(osrel.lines()
+                                                   .filter(|line| !line.trim().is_empty())
+                                                   .map(|line| Value::Text(line.to_string()))
+                                                   .collect())));
+        }
+        fields.push(Field::new("sections", "Sections", self.pe.section_groups()));
+        fields
+    }
+
+    fn warnings(&self) -> Vec<String> {
+        self.truncated.clone()
+    }
+}
+
+impl fmt::Display for Uki {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        super::fields::write_text(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parsers::builders::pe32_plus;
+    use parsers::BootInfo;
+
+    /// A UKI whose sections hold the given contents, one after the other from 0x400
+    fn uki(sections: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut layout = vec![];
+        let mut offset = 0x400u32;
+        for &(name, contents) in sections {
+            let len = contents.len() as u32;
+            layout.push((name, offset + 0x1000, len, offset, len));
+            offset += (contents.len() as u32 + 0x1ff) & !0x1ff;
+        }
+        let mut data = pe32_plus(0x80, 0x1400, &layout);
+        data.resize(offset as usize, 0);
+        for (&(_, contents), &(_, _, _, offset, _)) in sections.iter().zip(&layout) {
+            data[offset as usize..offset as usize + contents.len()].copy_from_slice(contents);
+        }
+        data
+    }
+
+    const OSREL: &[u8] = b"NAME=\"Test Linux\"\nID=test\nVERSION_ID=1\n";
+
+    #[test]
+    fn parse_uki() {
+        let data = uki(&[(".text", &[0xc3; 16]),
+                         (".osrel", OSREL),
+                         (".cmdline", b"root=/dev/sda1 quiet\0"),
+                         (".uname", b"6.9.0-test\n"),
+                         (".linux", b"MZ not really a kernel")]);
+        assert!(is_uki(&data));
+        let uki = Uki::parse(&data).unwrap();
+        assert_eq!(uki.cmdline(), Some("root=/dev/sda1 quiet"));
+        assert_eq!(uki.uname(), Some("6.9.0-test"));
+        assert_eq!(uki.osrel(), Some("NAME=\"Test Linux\"\nID=test\nVERSION_ID=1"));
+        assert_eq!(uki.linux_offset(), 0xc00);
+        assert_eq!(uki.linux(&data), b"MZ not really a kernel");
+        assert!(uki.warnings().is_empty());
+        let fields = uki.fields();
+        let osrel = fields.iter().find(|f| f.name == "osrel").unwrap();
+        assert_eq!(osrel.value.to_json(),
+                   json!(["NAME=\"Test Linux\"", "ID=test", "VERSION_ID=1"]));
+    }
+
+    #[test]
+    fn plain_efi_application_is_not_uki() {
+        let data = uki(&[(".text", &[0xc3; 16])]);
+        assert!(!is_uki(&data));
+        assert_eq!(Uki::parse(&data).unwrap_err(), Rejection::NotPresent);
+    }
+
+    #[test]
+    fn reject_truncated_linux_section() {
+        let data = uki(&[(".osrel", OSREL), (".linux", &[0u8; 0x100])]);
+        match Uki::parse(&data[..0x680]) {
+            Err(Rejection::Invalid { .. }) => {}
+            other => panic!("unexpected outcome {:?}", other),
+        }
+    }
+}
@@ -1,8 +1,113 @@
 use bytes::{self, BufMut};
+use bzip2;
+use decoders;
 use flate2;
+use lz4_flex;
+use ruzstd;
+use std::fmt;
 use std::io::{self, Read, Seek};
+use xz2;
 use super::{Result, ResultExt};
 
+/// A compressed container format that the header bytes were unwrapped from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Xz,
+    Bzip2,
+    Lzma,
+    Lz4,
+    /// The older LZ4 format that the Linux kernel compresses its payload with
+    Lz4Legacy,
+    Lzo,
+    Zstd,
+}
+
+/// The magic bytes that each compressed container format starts with
+const MAGICS: &[(&[u8], Compression)] = &[(b"\x1f\x8b", Compression::Gzip),
+                                          (b"\xfd7zXZ\x00", Compression::Xz),
+                                          // Followed by the block size and the block magic
+                                          (b"BZh", Compression::Bzip2),
+                                          // Properties byte for the default settings, followed
+                                          // by the low bytes of the dictionary size
+                                          (b"\x5d\x00\x00", Compression::Lzma),
+                                          (b"\x04\x22\x4d\x18", Compression::Lz4),
+                                          (b"\x02\x21\x4c\x18", Compression::Lz4Legacy),
+                                          (decoders::LZOP_MAGIC, Compression::Lzo),
+                                          (b"\x28\xb5\x2f\xfd", Compression::Zstd)];
+
+/// The magic that starts the first block of a bzip2 stream, after the block size digit
+const BZIP2_BLOCK_MAGIC: &[u8] = b"1AY&SY";
+
+/// The longest magic in `MAGICS`, including the rest of the bzip2 stream header
+const MAGIC_LEN: usize = 10;
+
+/// How much a decoder has to produce before the data is taken to be in its format
+const PROBE_LEN: usize = 512;
+
+/// The most memory that the xz and lzma decoders may use, which is well above what the largest
+/// dictionary `xz -9` picks needs
+const MAX_DECODER_MEMORY: u64 = 256 << 20;
+
+/// The most that a whole file, or the kernel payload inside it, is read or decompressed to, to
+/// bound memory use on hostile input
+pub const MAX_FILE_SIZE: u64 = 1 << 30;
+
+impl Compression {
+    /// Identify the compressed container format from the first bytes of the data
+    pub fn detect(data: &[u8]) -> Option<Compression> {
+        MAGICS.iter()
+            .find(|&&(magic, compression)| {
+                      data.starts_with(magic) &&
+                      (compression != Compression::Bzip2 ||
+                       data.get(3).is_some_and(|size| (b'1'..=b'9').contains(size)) &&
+                       data[4..].starts_with(BZIP2_BLOCK_MAGIC))
+                  })
+            .map(|&(_, compression)| compression)
+    }
+
+    /// Wrap a reader of the compressed data in one that produces the decompressed data
+    pub fn decoder<'a, R: Read + 'a>(self, rdr: R) -> Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            Compression::Gzip => Box::new(flate2::read::GzDecoder::new(rdr)),
+            Compression::Xz => {
+                let stream = xz2::stream::Stream::new_stream_decoder(MAX_DECODER_MEMORY, 0)
+                    .chain_err(|| "failed to create xz decoder")?;
+                Box::new(xz2::read::XzDecoder::new_stream(rdr, stream))
+            }
+            Compression::Bzip2 => Box::new(bzip2::read::BzDecoder::new(rdr)),
+            Compression::Lzma => {
+                let stream = xz2::stream::Stream::new_lzma_decoder(MAX_DECODER_MEMORY)
+                    .chain_err(|| "failed to create lzma decoder")?;
+                Box::new(xz2::read::XzDecoder::new_stream(rdr, stream))
+            }
+            Compression::Lz4 => Box::new(lz4_flex::frame::FrameDecoder::new(rdr)),
+            Compression::Lz4Legacy => Box::new(decoders::lz4_legacy(rdr)),
+            Compression::Lzo => Box::new(decoders::lzop(rdr)),
+            Compression::Zstd => {
+                let decoder = ruzstd::StreamingDecoder::new(rdr)
+                    .map_err(|e| format!("failed to read zstd frame header: {}", e))?;
+                Box::new(decoder)
+            }
+        })
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Compression::Gzip => write!(f, "gzip"),
+            Compression::Xz => write!(f, "xz"),
+            Compression::Bzip2 => write!(f, "bzip2"),
+            Compression::Lzma => write!(f, "lzma"),
+            Compression::Lz4 => write!(f, "lz4"),
+            Compression::Lz4Legacy => write!(f, "lz4 (legacy)"),
+            Compression::Lzo => write!(f, "lzo"),
+            Compression::Zstd => write!(f, "zstd"),
+        }
+    }
+}
+
 /// Create a buffer from the file
 pub fn create_buffer<R: Read>(rdr: R, buflen: usize) -> Result<bytes::Bytes> {
     let mut fp = rdr.take(buflen as u64);
@@ -17,19 +122,189 @@ pub fn create_buffer<R: Read>(rdr: R, buflen: usize) -> Result<bytes::Bytes> {
 
 /// Read out the possible header bytes from the file
 pub fn header_bytes<R: Read + Seek>(fp: R, buflen: usize) -> Result<bytes::Bytes> {
-    // Assume that it is GZip-encoded
-    let fp = flate2::read::GzDecoder::new(fp);
-    // If it was in fact GZip-encoded
-    if fp.header().is_some() {
-        // Create a buffer out of the uncompressed bytes
-        create_buffer(fp, buflen)
-    } else {
-        // Otherwise, we need to get back the original file
-        let mut fp = fp.into_inner();
-        // Rewind to the beginning of it
-        fp.seek(io::SeekFrom::Start(0))
-            .chain_err(|| "failed to seek back to beginning of file")?;
-        // And create a buffer from the uncompressed bytes
-        create_buffer(fp, buflen)
+    detect_header_bytes(fp, buflen).map(|(bytes, _)| bytes)
+}
+
+/// Read out the possible header bytes from the file, along with the compression that was removed
+/// to get at them
+///
+/// Offsets within the returned bytes are relative to the decompressed data when there was
+/// compression.
+pub fn detect_header_bytes<R: Read + Seek>(fp: R,
+                                           buflen: usize)
+                                           -> Result<(bytes::Bytes, Option<Compression>)> {
+    let (fp, compression) = decompressed(fp)?;
+    let bytes = match compression {
+        Some(compression) => {
+            create_buffer(fp, buflen)
+                .chain_err(|| format!("failed to decompress {} input", compression))?
+        }
+        None => create_buffer(fp, buflen)?,
+    };
+    Ok((bytes, compression))
+}
+
+/// Read the whole file, along with the compression that was removed to get at its contents
+///
+/// Files that are, or decompress to, more than `MAX_FILE_SIZE` bytes are an error.
+pub fn detect_file_bytes<R: Read + Seek>(fp: R) -> Result<(bytes::Bytes, Option<Compression>)> {
+    file_bytes_up_to(fp, MAX_FILE_SIZE)
+}
+
+fn file_bytes_up_to<R: Read + Seek>(fp: R,
+                                    limit: u64)
+                                    -> Result<(bytes::Bytes, Option<Compression>)> {
+    let (fp, compression) = decompressed(fp)?;
+    let mut buffer = vec![];
+    fp.take(limit + 1)
+        .read_to_end(&mut buffer)
+        .chain_err(|| match compression {
+                       Some(compression) => format!("failed to decompress {} input", compression),
+                       None => "failed to read input file".to_string(),
+                   })?;
+    if buffer.len() as u64 > limit {
+        return Err(format!("input is larger than the limit of {} bytes{}",
+                           limit,
+                           compression.map_or_else(String::new,
+                                                   |c| format!(" once {}-decompressed", c)))
+                           .into());
+    }
+    Ok((buffer.into(), compression))
+}
+
+/// Wrap the file in a decoder for its compressed container format, if it has one
+fn decompressed<'a, R: Read + Seek + 'a>(mut fp: R)
+                                         -> Result<(Box<dyn Read + 'a>, Option<Compression>)> {
+    // Peek at the start of the file for the magic of a compressed container
+    let magic = create_buffer(&mut fp, MAGIC_LEN)?;
+    // Rewind to the beginning of it
+    fp.seek(io::SeekFrom::Start(0))
+        .chain_err(|| "failed to seek back to beginning of file")?;
+
+    // A short magic can begin an image that is not compressed at all, so only go with a format
+    // once its decoder gets through the start of the data
+    match Compression::detect(&magic) {
+        Some(compression) if decodes(compression, &mut fp)? => {
+            Ok((compression.decoder(fp)?, Some(compression)))
+        }
+        _ => Ok((Box::new(fp), None)),
+    }
+}
+
+/// Whether the file decodes as `compression` for at least its first few bytes, leaving the file
+/// rewound
+fn decodes<R: Read + Seek>(compression: Compression, fp: &mut R) -> Result<bool> {
+    let ok = compression.decoder(&mut *fp)
+        .and_then(|decoder| create_buffer(decoder, PROBE_LEN))
+        .is_ok_and(|start| !start.is_empty());
+    fp.seek(io::SeekFrom::Start(0))
+        .chain_err(|| "failed to seek back to beginning of file")?;
+    Ok(ok)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const MULTIBOOT1: &[u8; 40000] = include_bytes!("../test-data/multiboot1");
+    const LINUXBOOT: &[u8; 40000] = include_bytes!("../test-data/linuxboot");
+
+    #[test]
+    fn header_bytes_of_plain_file() {
+        let bytes = header_bytes(io::Cursor::new(LINUXBOOT.as_ref()), 8192).unwrap();
+        assert_eq!(&bytes[..], &LINUXBOOT[..8192]);
+    }
+
+    #[test]
+    fn header_bytes_of_gzip_file() {
+        let (bytes, compression) = detect_header_bytes(io::Cursor::new(MULTIBOOT1.as_ref()), 8192)
+            .unwrap();
+        assert_eq!(compression, Some(Compression::Gzip));
+        assert_eq!(bytes.len(), 8192);
+        assert_eq!(&bytes[..4], b"\x7fELF");
+    }
+
+    fn check_compressed(data: &[u8], expected: Compression) {
+        let (bytes, compression) = detect_header_bytes(io::Cursor::new(data), 8192).unwrap();
+        assert_eq!(compression, Some(expected));
+        assert_eq!(&bytes[..], &LINUXBOOT[..8192]);
+
+        // The kernel inside is described just as the uncompressed one is
+        let linux = ::parsers::register()
+            .into_iter()
+            .find(|desc| desc.name == "linux")
+            .unwrap();
+        let bytes = header_bytes(io::Cursor::new(data), linux.max_range).unwrap();
+        assert_eq!(&bytes[..], &LINUXBOOT[..linux.max_range]);
+        assert_eq!(linux.parse(bytes).unwrap().format(), "linux");
+    }
+
+    #[test]
+    fn header_bytes_of_xz_file() {
+        check_compressed(include_bytes!("../test-data/linuxboot.xz"), Compression::Xz);
+    }
+
+    #[test]
+    fn header_bytes_of_bzip2_file() {
+        check_compressed(include_bytes!("../test-data/linuxboot.bz2"), Compression::Bzip2);
+    }
+
+    #[test]
+    fn header_bytes_of_lzma_file() {
+        check_compressed(include_bytes!("../test-data/linuxboot.lzma"), Compression::Lzma);
+    }
+
+    #[test]
+    fn header_bytes_of_lz4_file() {
+        check_compressed(include_bytes!("../test-data/linuxboot.lz4"), Compression::Lz4);
+    }
+
+    #[test]
+    fn header_bytes_of_legacy_lz4_file() {
+        check_compressed(include_bytes!("../test-data/linuxboot-legacy.lz4"),
+                         Compression::Lz4Legacy);
+    }
+
+    #[test]
+    fn header_bytes_of_zstd_file() {
+        check_compressed(include_bytes!("../test-data/linuxboot.zst"), Compression::Zstd);
+    }
+
+    /// An uncompressed image that starts with `magic`, with a Multiboot header at 0x40
+    fn plain_image_starting_with(magic: &[u8]) -> Vec<u8> {
+        let mut data = vec![0u8; 0x1000];
+        data[..magic.len()].copy_from_slice(magic);
+        let checksum = 0u32.wrapping_sub(::parsers::multiboot1::MAGIC);
+        data[0x40..0x44].copy_from_slice(&::parsers::multiboot1::MAGIC.to_le_bytes());
+        data[0x48..0x4c].copy_from_slice(&checksum.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn header_bytes_of_plain_file_with_compression_magic() {
+        for magic in &[&b"BZh91AY&SY"[..], b"\x5d\x00\x00", b"\x1f\x8b", b"\xfd7zXZ\x00"] {
+            let data = plain_image_starting_with(magic);
+            let (bytes, compression) = detect_header_bytes(io::Cursor::new(&data[..]), 8192)
+                .unwrap();
+            assert_eq!(compression, None);
+            assert_eq!(&bytes[..], &data[..]);
+            ::parsers::multiboot1::Header::parse(bytes).unwrap();
+        }
+    }
+
+    #[test]
+    fn detect_bzip2_by_block_magic() {
+        assert_eq!(Compression::detect(b"BZh91AY&SY"), Some(Compression::Bzip2));
+        assert_eq!(Compression::detect(b"BZh01AY&SY"), None);
+        assert_eq!(Compression::detect(b"BZh9\x00\x00\x00\x00\x00\x00"), None);
+    }
+
+    #[test]
+    fn limit_file_bytes() {
+        let data = include_bytes!("../test-data/linuxboot.xz");
+        let (bytes, _) = file_bytes_up_to(io::Cursor::new(data.as_ref()), 1 << 20).unwrap();
+        assert_eq!(&bytes[..], &LINUXBOOT[..bytes.len()]);
+        let err = file_bytes_up_to(io::Cursor::new(data.as_ref()), 4096).unwrap_err();
+        assert_eq!(err.to_string(),
+                   "input is larger than the limit of 4096 bytes once xz-decompressed");
     }
 }